@@ -1,38 +1,144 @@
 use anyhow::Result;
+use chrono::{DateTime, Local};
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tokio::sync::{mpsc, Mutex};
-use tokio::time::Duration;
+use tokio::sync::{broadcast, mpsc, oneshot, watch, Mutex};
+use tokio::task::{AbortHandle, JoinHandle};
+use tokio::time::{Duration, Instant};
+use uuid::Uuid;
 
-use crate::models::{Button, ButtonAction, ButtonActionType, MidiMessage, Preset};
-use crate::tcp_client::LightingControllerClient;
+use crate::history::TriggerSource;
+use crate::models::{
+    Button, ButtonActionType, ControllerAction, GestureTimings, HeartbeatSettings, MidiMessage,
+    MidiTrigger, NoteGesture, Preset, PresetAction, RetriggerPolicy,
+};
+use crate::tcp_client::{LightingControllerClient, LiveMessage};
+use crate::tempo::SharedTempo;
+use crate::timecode::{FrameRate, Timecode, TimecodeChaser};
 
 pub enum ActionCommand {
-    ExecutePreset(Preset),
-    ExecuteSingle(ButtonAction),
-    ConnectionSuccess(Vec<Button>),
-    ConnectionError(String),
+    ExecutePreset(Preset, TriggerSource),
     Connect(String, String),
     Disconnect,
+    SendBpm(f32),
+    SendBeat,
+    /// Move a controller fader right away, bypassing the preset queues
+    SetFader { index: u32, value: i32 },
+    /// Abort every running and queued preset execution
+    StopAll,
+    /// Change how the controller link is checked, applied from the next heartbeat
+    SetHeartbeat(HeartbeatSettings),
 }
 
+/// Something the executor or matcher reports back to the UI
+pub struct ExecutorEvent {
+    pub at: DateTime<Local>,
+    pub kind: EventKind,
+}
+
+impl ExecutorEvent {
+    pub fn now(kind: EventKind) -> Self {
+        Self { at: Local::now(), kind }
+    }
+}
+
+pub enum EventKind {
+    /// Button list fetched from the controller, on connect and every refresh
+    ButtonsLoaded(Vec<Button>),
+    Link(LinkState),
+    Disconnected,
+    PresetTriggered { name: String, cause: String },
+    /// `execution` identifies this run of the preset in the events that follow
+    PresetStarted { execution: u64, preset_id: Uuid, name: String, trigger: TriggerSource },
+    /// A command was written to the controller; the ids are `None` for single actions
+    ActionSent { execution: Option<u64>, preset_id: Option<Uuid>, action: String },
+    ActionFailed { execution: Option<u64>, preset_id: Option<Uuid>, action: String, error: String },
+    PresetFinished { execution: u64, preset_id: Uuid, name: String, outcome: PresetOutcome },
+    /// A command sent to the executor failed outside of any preset
+    CommandFailed(String),
+    /// Result of a heartbeat; `rtt` is `None` when it went unanswered, and
+    /// `misses` counts unanswered heartbeats in a row
+    Heartbeat { rtt: Option<Duration>, misses: u32 },
+    /// Anything the controller sent, including button presses and fader moves made on it
+    ControllerMessage(LiveMessage),
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum PresetOutcome {
+    Completed,
+    /// Stopped at the first action that couldn't be sent
+    Failed(String),
+    /// Aborted by a retrigger, another preset in its group or Stop All
+    Cancelled,
+}
+
+impl PresetOutcome {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PresetOutcome::Completed => "Completed",
+            PresetOutcome::Failed(_) => "Failed",
+            PresetOutcome::Cancelled => "Cancelled",
+        }
+    }
+}
+
+/// Where the connection supervisor is at
+pub enum LinkState {
+    Connecting { attempt: u32 },
+    Connected,
+    /// Waiting until `retry_at` before the next attempt
+    BackingOff { attempt: u32, retry_at: DateTime<Local>, error: String },
+    /// Too many attempts in a row failed; a new Connect starts over
+    GaveUp(String),
+}
+
+/// Attempts in a row before the supervisor gives up
+const MAX_CONNECT_ATTEMPTS: u32 = 10;
+
+/// Wait after the first failed attempt, doubled for each one after it
+const BACKOFF_BASE: Duration = Duration::from_secs(1);
+
+const BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+type EventSender = mpsc::UnboundedSender<ExecutorEvent>;
+
 pub struct ActionExecutor {
-    client: Option<Arc<Mutex<LightingControllerClient>>>,
+    /// Task keeping the controller connection up, if one was requested
+    supervisor: Option<AbortHandle>,
+    heartbeat: watch::Sender<HeartbeatSettings>,
+    tempo: SharedTempo,
     rx: mpsc::UnboundedReceiver<ActionCommand>,
-    tx: mpsc::UnboundedSender<ActionCommand>,
+    events: EventSender,
+    output: ControllerOutput,
+    /// Preset executions still in flight, keyed by preset id
+    running: HashMap<Uuid, RunningPreset>,
+    next_execution: u64,
 }
 
 impl ActionExecutor {
     pub fn new(
         rx: mpsc::UnboundedReceiver<ActionCommand>,
-        tx: mpsc::UnboundedSender<ActionCommand>,
+        events: EventSender,
+        tempo: SharedTempo,
     ) -> Self {
-        Self { client: None, rx, tx }
+        Self {
+            supervisor: None,
+            heartbeat: watch::channel(HeartbeatSettings::default()).0,
+            tempo,
+            rx,
+            output: ControllerOutput::new(events.clone()),
+            events,
+            running: HashMap::new(),
+            next_execution: 0,
+        }
     }
 
     pub async fn run(&mut self) {
         while let Some(cmd) = self.rx.recv().await {
             if let Err(e) = self.handle_command(cmd).await {
-                eprintln!("Action executor error: {}", e);
+                let _ = self.events.send(ExecutorEvent::now(EventKind::CommandFailed(e.to_string())));
             }
         }
     }
@@ -40,131 +146,1113 @@ impl ActionExecutor {
     async fn handle_command(&mut self, cmd: ActionCommand) -> Result<()> {
         match cmd {
             ActionCommand::Connect(addr, password) => {
-                match LightingControllerClient::connect(&addr, &password).await {
-                    Ok(client) => {
-                        let client_ref = Arc::new(Mutex::new(client));
-                        self.client = Some(Arc::clone(&client_ref));
-
-                        let tx_clone = self.tx.clone();
-                        let client_ref_clone = Arc::clone(&client_ref);
-
-                        // Immediately fetch button list
-                        match client_ref_clone.lock().await.button_list().await {
-                            Ok(buttons) => {
-                                let _ = tx_clone.send(ActionCommand::ConnectionSuccess(buttons));
-                            }
-                            Err(e) => {
-                                let _ = tx_clone.send(ActionCommand::ConnectionError(e.to_string()));
-                            }
-                        }
+                // A new connection replaces whatever the old supervisor was doing
+                self.stop_supervisor();
+                let supervisor = tokio::spawn(supervise_connection(
+                    addr,
+                    password,
+                    self.output.clone(),
+                    self.events.clone(),
+                    self.heartbeat.subscribe(),
+                    self.tempo.clone(),
+                ));
+                self.supervisor = Some(supervisor.abort_handle());
+            }
 
-                        // Start periodic refresh
-                        tokio::spawn(async move {
-                            loop {
-                                tokio::time::sleep(Duration::from_secs(10)).await;
-
-                                let mut client_guard = client_ref.lock().await;
-                                if let Err(e) = client_guard
-                                    .button_list()
-                                    .await
-                                    .map(|buttons| {
-                                        let _ = tx_clone.send(ActionCommand::ConnectionSuccess(buttons));
-                                    })
-                                {
-                                    let _ = tx_clone.send(ActionCommand::ConnectionError(e.to_string()));
-                                }
-                            }
-                        });
-                    }
-                    Err(e) => {
-                        let _ = self.tx.send(ActionCommand::ConnectionError(e.to_string()));
-                    }
-                }
+            ActionCommand::ExecutePreset(preset, trigger) => {
+                self.start_preset(preset, trigger);
+            }
+
+            ActionCommand::SetHeartbeat(settings) => {
+                self.heartbeat.send_replace(settings);
             }
 
-            ActionCommand::ExecutePreset(preset) => {
-                // Wait for preset delay before executing actions
-                if preset.delay_secs > 0.0 {
-                    tokio::time::sleep(Duration::from_secs_f32(preset.delay_secs)).await;
+            ActionCommand::StopAll => {
+                self.running.retain(|_, running| running.prune());
+                for (preset_id, running) in self.running.drain() {
+                    running.cancel(preset_id, &self.events);
                 }
-                self.execute_actions(&preset.actions).await?;
+                self.output.stop();
+            }
+
+            // Writes go through the output so a stalled connection never
+            // holds up the commands behind them, Stop All and Disconnect included
+            ActionCommand::SendBpm(bpm) => {
+                self.output.send_command(ControllerAction::Bpm { bpm })?;
+            }
+
+            ActionCommand::SendBeat => {
+                self.output.send_command(ControllerAction::Beat)?;
             }
 
-            ActionCommand::ExecuteSingle(action) => {
-                self.execute_action(&action).await?;
+            ActionCommand::SetFader { index, value } => {
+                self.output.set_fader(index, value)?;
             }
 
             ActionCommand::Disconnect => {
-                // Clear the client connection
-                self.client = None;
+                // Stop reconnecting and clear the client connection
+                self.stop_supervisor();
+                self.output.set_client(None);
                 // Notify UI that we've disconnected
-                let _ = self.tx.send(ActionCommand::ConnectionSuccess(Vec::new()));
+                let _ = self.events.send(ExecutorEvent::now(EventKind::Disconnected));
             }
+        }
+
+        Ok(())
+    }
 
-            ActionCommand::ConnectionSuccess(_) | ActionCommand::ConnectionError(_) => {
-                println!("Connection event handled by UI thread");
+    /// Each execution runs on its own task so long delays never hold up other presets
+    fn start_preset(&mut self, preset: Preset, trigger: TriggerSource) {
+        self.running.retain(|_, running| running.prune());
+
+        // Starting a preset takes over from the rest of its group
+        if let Some(group) = &preset.group {
+            let events = &self.events;
+            let taken_over: Vec<Uuid> = self
+                .running
+                .iter()
+                .filter(|(id, running)| **id != preset.id && running.group.as_ref() == Some(group))
+                .map(|(id, _)| *id)
+                .collect();
+            for id in taken_over {
+                if let Some(running) = self.running.remove(&id) {
+                    running.cancel(id, events);
+                }
             }
         }
 
-        Ok(())
+        let previous = match (self.running.remove(&preset.id), preset.retrigger) {
+            (Some(running), RetriggerPolicy::Restart) => {
+                running.cancel(preset.id, &self.events);
+                None
+            }
+            (Some(running), RetriggerPolicy::Ignore) => {
+                self.running.insert(preset.id, running);
+                return;
+            }
+            (Some(running), RetriggerPolicy::Queue) => Some(running),
+            (None, _) => None,
+        };
+
+        let execution = self.next_execution;
+        self.next_execution += 1;
+        let preset_id = preset.id;
+        let name = preset.name.clone();
+        let group = preset.group.clone();
+        let output = self.output.clone();
+        let events = self.events.clone();
+        let (mut tasks, wait_for) = match previous {
+            Some(running) => (running.tasks, running.last),
+            None => (Vec::new(), None),
+        };
+
+        let handle = tokio::spawn(async move {
+            if let Some(wait_for) = wait_for {
+                let _ = wait_for.await;
+            }
+            run_preset(execution, preset, trigger, output, events).await;
+        });
+        tasks.push((execution, handle.abort_handle()));
+        self.running.insert(
+            preset_id,
+            RunningPreset {
+                name,
+                group,
+                tasks,
+                last: Some(handle),
+            },
+        );
+    }
+
+    fn stop_supervisor(&mut self) {
+        if let Some(supervisor) = self.supervisor.take() {
+            supervisor.abort();
+        }
+    }
+}
+
+/// Keep a connection to the controller up: connect, send heartbeats while
+/// the link holds, and reconnect with exponential backoff when it
+/// drops, until too many attempts in a row have failed.
+async fn supervise_connection(
+    addr: String,
+    password: String,
+    output: ControllerOutput,
+    events: EventSender,
+    heartbeat_settings: watch::Receiver<HeartbeatSettings>,
+    tempo: SharedTempo,
+) {
+    let link = |state: LinkState| {
+        let _ = events.send(ExecutorEvent::now(EventKind::Link(state)));
+    };
+
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        link(LinkState::Connecting { attempt });
+
+        let error = match LightingControllerClient::connect(&addr, &password, tempo.clone()).await {
+            Ok(client) => {
+                attempt = 0;
+                let messages = client.subscribe();
+                let closed = client.closed();
+                let client = Arc::new(Mutex::new(client));
+                output.set_client(Some(Arc::clone(&client)));
+                output.follow_controller(messages);
+                link(LinkState::Connected);
+
+                let error = tokio::select! {
+                    _ = closed => "Connection closed".to_string(),
+                    e = heartbeat(&client, &events, &heartbeat_settings) => e,
+                };
+                output.set_client(None);
+                error
+            }
+            Err(e) => e.to_string(),
+        };
+
+        if attempt >= MAX_CONNECT_ATTEMPTS {
+            link(LinkState::GaveUp(error));
+            return;
+        }
+
+        let delay = backoff_delay(attempt.max(1));
+        link(LinkState::BackingOff {
+            attempt: attempt.max(1),
+            retry_at: Local::now() + chrono::Duration::from_std(delay).unwrap_or_default(),
+            error,
+        });
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Probe the controller until it stops answering, returning why the link is
+/// considered dead. The probe is a button list request, the one request the
+/// controller always answers, so it also keeps the button list up to date.
+async fn heartbeat(
+    client: &Mutex<LightingControllerClient>,
+    events: &EventSender,
+    settings: &watch::Receiver<HeartbeatSettings>,
+) -> String {
+    let mut misses = 0;
+    loop {
+        let current = *settings.borrow();
+        let limit = Duration::from_millis(current.timeout_ms as u64);
+        let probe = async {
+            // Only hold the client while writing, not while the reply is on its way
+            let (sent, reply) = {
+                let mut client = client.lock().await;
+                (Instant::now(), client.request_button_list().await?)
+            };
+            let buttons = reply.button_list(limit).await?;
+            Ok::<_, anyhow::Error>((sent.elapsed(), buttons))
+        };
+
+        // Waiting for the client and the write count too, so a peer that
+        // stopped reading shows up as a miss instead of blocking forever
+        let result = tokio::time::timeout(limit, probe)
+            .await
+            .unwrap_or_else(|_| Err(anyhow::anyhow!("No reply within {} ms", limit.as_millis())));
+        match result {
+            Ok((rtt, buttons)) => {
+                misses = 0;
+                let _ = events.send(ExecutorEvent::now(EventKind::Heartbeat { rtt: Some(rtt), misses }));
+                let _ = events.send(ExecutorEvent::now(EventKind::ButtonsLoaded(buttons)));
+            }
+            Err(e) => {
+                misses += 1;
+                let _ = events.send(ExecutorEvent::now(EventKind::Heartbeat { rtt: None, misses }));
+                if misses >= current.max_misses.max(1) {
+                    return format!("No reply to {} heartbeats in a row: {}", misses, e);
+                }
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(current.interval_ms as u64)).await;
+    }
+}
+
+/// Exponential backoff for the given failed attempt, with random jitter so
+/// several clients don't retry in lockstep
+fn backoff_delay(attempt: u32) -> Duration {
+    let exponent = attempt.saturating_sub(1).min(16);
+    let delay = (BACKOFF_BASE * 2u32.pow(exponent)).min(BACKOFF_MAX);
+
+    // Anywhere between half and the full delay
+    let random = std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish();
+    let jitter = (random % 1000) as f32 / 1000.0;
+    delay.mul_f32(0.5 + jitter * 0.5)
+}
+
+/// Executions of one preset that are still pending or in flight
+struct RunningPreset {
+    name: String,
+    group: Option<String>,
+    /// Abort handles keyed by execution id
+    tasks: Vec<(u64, AbortHandle)>,
+    /// The most recent execution, which a queued retrigger waits for
+    last: Option<JoinHandle<()>>,
+}
+
+impl RunningPreset {
+    /// Drop finished executions, returning whether any are left
+    fn prune(&mut self) -> bool {
+        self.tasks.retain(|(_, task)| !task.is_finished());
+        !self.tasks.is_empty()
+    }
+
+    /// Abort every execution and report the unfinished ones as cancelled
+    fn cancel(self, preset_id: Uuid, events: &EventSender) {
+        for (execution, task) in &self.tasks {
+            if task.is_finished() {
+                continue;
+            }
+            task.abort();
+            let _ = events.send(ExecutorEvent::now(EventKind::PresetFinished {
+                execution: *execution,
+                preset_id,
+                name: self.name.clone(),
+                outcome: PresetOutcome::Cancelled,
+            }));
+        }
     }
+}
 
-    async fn execute_actions(&mut self, actions: &[ButtonAction]) -> Result<()> {
-        for action in actions {
+async fn run_preset(
+    execution: u64,
+    preset: Preset,
+    trigger: TriggerSource,
+    output: ControllerOutput,
+    events: EventSender,
+) {
+    let _ = events.send(ExecutorEvent::now(EventKind::PresetStarted {
+        execution,
+        preset_id: preset.id,
+        name: preset.name.clone(),
+        trigger,
+    }));
+
+    let outcome = async {
+        // Wait for preset delay before executing actions
+        if preset.delay_secs > 0.0 {
+            tokio::time::sleep(Duration::from_secs_f32(preset.delay_secs)).await;
+        }
+        for action in &preset.actions {
             if action.delay_secs > 0.0 {
                 tokio::time::sleep(Duration::from_secs_f32(action.delay_secs)).await;
             }
-            self.execute_action(action).await?;
+            // Wait for the write so a failure stops the rest of the preset
+            let written = output.send(action);
+            if let Err(e) = report_write(&events, Some(execution), Some(preset.id), action, written).await {
+                return PresetOutcome::Failed(e);
+            }
+        }
+        PresetOutcome::Completed
+    }
+    .await;
+
+    let _ = events.send(ExecutorEvent::now(EventKind::PresetFinished {
+        execution,
+        preset_id: preset.id,
+        name: preset.name,
+        outcome,
+    }));
+}
+
+/// Wait for a queued action to be written and report how it went
+async fn report_write(
+    events: &EventSender,
+    execution: Option<u64>,
+    preset_id: Option<Uuid>,
+    action: &PresetAction,
+    written: Result<WriteReceipt>,
+) -> Result<(), String> {
+    let result = match written {
+        Ok(receipt) => receipt
+            .await
+            .unwrap_or_else(|_| Err("Connection closed before the command was sent".to_string())),
+        Err(e) => Err(e.to_string()),
+    };
+
+    let action = action.command.display_name();
+    match &result {
+        Ok(at) => {
+            let _ = events.send(ExecutorEvent {
+                at: *at,
+                kind: EventKind::ActionSent { execution, preset_id, action },
+            });
+        }
+        Err(error) => {
+            let _ = events.send(ExecutorEvent::now(EventKind::ActionFailed {
+                execution,
+                preset_id,
+                action,
+                error: error.clone(),
+            }));
+        }
+    }
+    result.map(|_| ())
+}
+
+/// Resolves with the time an action was written to the controller, or why it wasn't
+type WriteReceipt = oneshot::Receiver<Result<DateTime<Local>, String>>;
+
+/// An action waiting in a command queue, with where to report its write
+struct QueuedAction {
+    command: ControllerAction,
+    written: oneshot::Sender<Result<DateTime<Local>, String>>,
+}
+
+/// A command queue in front of the client
+struct CommandQueue {
+    tx: mpsc::UnboundedSender<QueuedAction>,
+    /// Set by Stop All; whatever is still queued is dropped instead of sent
+    stopped: Arc<AtomicBool>,
+}
+
+/// Everything sent to the controller on behalf of presets and mappings.
+///
+/// There is one queue per button in front of the client, plus one shared
+/// queue for commands that aren't tied to a button. Actions for the same
+/// button are written in the order they were issued, while different buttons
+/// never wait behind each other's queue. Fades run on their own tasks, one
+/// per fader, and direct fader moves go through one writer per fader that
+/// always sends the latest value.
+#[derive(Clone)]
+struct ControllerOutput {
+    inner: Arc<std::sync::Mutex<QueueState>>,
+    events: EventSender,
+}
+
+#[derive(Default)]
+struct QueueState {
+    client: Option<Arc<Mutex<LightingControllerClient>>>,
+    /// Keyed by button name, `None` for BPM, Beat, Freeze and Auto-BPM
+    queues: HashMap<Option<String>, CommandQueue>,
+    /// Running fade per fader, with an id so a finished fade only clears itself
+    fades: HashMap<u32, (u64, AbortHandle)>,
+    next_fade_id: u64,
+    /// Last value sent to each fader, where fades without a start value begin
+    fader_values: HashMap<u32, i32>,
+    /// Writer task per fader for direct moves, fed the newest value
+    fader_writers: HashMap<u32, watch::Sender<i32>>,
+}
+
+impl QueueState {
+    fn connected_client(&self) -> Result<Arc<Mutex<LightingControllerClient>>> {
+        self.client
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Not connected"))
+    }
+
+    fn cancel_fade(&mut self, fader: u32) {
+        if let Some((_, fade)) = self.fades.remove(&fader) {
+            fade.abort();
+        }
+    }
+}
+
+impl ControllerOutput {
+    fn new(events: EventSender) -> Self {
+        Self {
+            inner: Arc::default(),
+            events,
+        }
+    }
+
+    /// Switch to a new connection; queues for the old one drain and stop
+    fn set_client(&self, client: Option<Arc<Mutex<LightingControllerClient>>>) {
+        let mut state = self.inner.lock().unwrap();
+        state.client = client;
+        state.queues.clear();
+        state.fader_values.clear();
+        state.fader_writers.clear();
+        for (_, (_, fade)) in state.fades.drain() {
+            fade.abort();
+        }
+    }
+
+    /// Pass controller messages on to the UI, keeping fader values in step
+    /// with faders moved on the controller itself
+    fn follow_controller(&self, mut messages: broadcast::Receiver<LiveMessage>) {
+        let output = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let msg = match messages.recv().await {
+                    Ok(msg) => msg,
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                if let LiveMessage::FaderChange { index, value } = msg {
+                    output.inner.lock().unwrap().fader_values.insert(index, value);
+                }
+                let _ = output.events.send(ExecutorEvent::now(EventKind::ControllerMessage(msg)));
+            }
+        });
+    }
+
+    /// Drop every queued command and stop running fades. A command already
+    /// being written still goes out whole.
+    fn stop(&self) {
+        let mut state = self.inner.lock().unwrap();
+        for (_, queue) in state.queues.drain() {
+            queue.stopped.store(true, Ordering::Relaxed);
+        }
+        for (_, (_, fade)) in state.fades.drain() {
+            fade.abort();
+        }
+    }
+
+    /// Move a fader right away; this takes the fader over from any running fade.
+    /// The write happens on the fader's writer task, which skips values
+    /// superseded while it waited for the connection.
+    fn set_fader(&self, fader: u32, value: i32) -> Result<()> {
+        let mut state = self.inner.lock().unwrap();
+        state.cancel_fade(fader);
+        state.fader_values.insert(fader, value);
+        let client = state.connected_client()?;
+
+        if let Some(writer) = state.fader_writers.get(&fader) {
+            if writer.send(value).is_ok() {
+                return Ok(());
+            }
         }
+        let (writer, mut values) = watch::channel(value);
+        state.fader_writers.insert(fader, writer);
+
+        let events = self.events.clone();
+        tokio::spawn(async move {
+            loop {
+                let value = *values.borrow_and_update();
+                if let Err(e) = client.lock().await.send_fader(fader, value).await {
+                    let _ = events.send(ExecutorEvent::now(EventKind::ActionFailed {
+                        execution: None,
+                        preset_id: None,
+                        action: format!("Fader {} = {}", fader, value),
+                        error: e.to_string(),
+                    }));
+                    break;
+                }
+                // Ends once the connection is replaced and the sender dropped
+                if values.changed().await.is_err() {
+                    break;
+                }
+            }
+        });
         Ok(())
     }
 
-    async fn execute_action(&mut self, action: &ButtonAction) -> Result<()> {
-        let client = self
-            .client
-            .as_ref()
-            .ok_or_else(|| anyhow::anyhow!("Not connected"))?;
+    /// Queue a command that isn't part of a preset, reporting a failed write
+    fn send_command(&self, command: ControllerAction) -> Result<()> {
+        let action = PresetAction { command, delay_secs: 0.0 };
+        let written = self.send(&action)?;
+        let events = self.events.clone();
+        tokio::spawn(async move {
+            if let Ok(Err(e)) = written.await {
+                let _ = events.send(ExecutorEvent::now(EventKind::CommandFailed(format!(
+                    "{}: {}",
+                    action.command.display_name(),
+                    e
+                ))));
+            }
+        });
+        Ok(())
+    }
+
+    fn send(&self, action: &PresetAction) -> Result<WriteReceipt> {
+        let (written, receipt) = oneshot::channel();
+        if let ControllerAction::Fade { .. } = action.command {
+            // A fade counts as sent once it has started
+            self.start_fade(&action.command)?;
+            let _ = written.send(Ok(Local::now()));
+            return Ok(receipt);
+        }
 
-        let mut client = client.lock().await;
+        let mut state = self.inner.lock().unwrap();
+        let client = state.connected_client()?;
 
         // Use button_name instead of numeric ID
-        let button_name = &action.button_name;
+        let key = action.command.button_name().map(str::to_string);
+        let queue = state
+            .queues
+            .entry(key)
+            .or_insert_with(|| spawn_command_queue(client));
+        queue
+            .tx
+            .send(QueuedAction {
+                command: action.command.clone(),
+                written,
+            })
+            .map_err(|_| anyhow::anyhow!("Queue for {} closed", action.command.display_name()))?;
+        Ok(receipt)
+    }
 
-        match action.action {
-            ButtonActionType::Press => client.button_press(button_name).await?,
-            ButtonActionType::Release => client.button_release(button_name).await?,
-            ButtonActionType::Toggle => client.button_toggle(button_name).await?,
-        }
+    /// Start a fade, taking the fader over from any fade already running on it
+    fn start_fade(&self, command: &ControllerAction) -> Result<()> {
+        let ControllerAction::Fade { fader, from, to, duration_secs, curve, rate_hz } = *command else {
+            return Ok(());
+        };
+
+        let mut state = self.inner.lock().unwrap();
+        let client = state.connected_client()?;
+        state.cancel_fade(fader);
 
+        let from = from
+            .or_else(|| state.fader_values.get(&fader).copied())
+            .unwrap_or(0);
+        let fade_id = state.next_fade_id;
+        state.next_fade_id += 1;
+
+        let output = self.clone();
+        let handle = tokio::spawn(async move {
+            let interval = Duration::from_secs_f32(1.0 / rate_hz.max(1) as f32);
+            let mut ticker = tokio::time::interval(interval);
+            let started = Instant::now();
+            loop {
+                ticker.tick().await;
+                let progress = if duration_secs > 0.0 {
+                    (started.elapsed().as_secs_f32() / duration_secs).min(1.0)
+                } else {
+                    1.0
+                };
+                let value = from + ((to - from) as f32 * curve.apply(progress)).round() as i32;
+
+                output.inner.lock().unwrap().fader_values.insert(fader, value);
+                if let Err(e) = client.lock().await.send_fader(fader, value).await {
+                    let _ = output.events.send(ExecutorEvent::now(EventKind::ActionFailed {
+                        execution: None,
+                        preset_id: None,
+                        action: format!("Fade Fader {}", fader),
+                        error: e.to_string(),
+                    }));
+                    break;
+                }
+                if progress >= 1.0 {
+                    break;
+                }
+            }
+
+            let mut state = output.inner.lock().unwrap();
+            if matches!(state.fades.get(&fader), Some((id, _)) if *id == fade_id) {
+                state.fades.remove(&fader);
+            }
+        });
+        state.fades.insert(fader, (fade_id, handle.abort_handle()));
         Ok(())
     }
 }
 
+fn spawn_command_queue(client: Arc<Mutex<LightingControllerClient>>) -> CommandQueue {
+    let (tx, mut rx) = mpsc::unbounded_channel::<QueuedAction>();
+    let stopped = Arc::new(AtomicBool::new(false));
+    let queue = CommandQueue {
+        tx,
+        stopped: Arc::clone(&stopped),
+    };
+    tokio::spawn(async move {
+        while let Some(queued) = rx.recv().await {
+            let mut client = client.lock().await;
+            // Checked once the client is ours, as Stop All may come while waiting for it
+            if stopped.load(Ordering::Relaxed) {
+                let _ = queued.written.send(Err("Stopped".to_string()));
+                continue;
+            }
+            let result = send_controller_action(&mut client, &queued.command)
+                .await
+                .map(|_| Local::now())
+                .map_err(|e| e.to_string());
+            let _ = queued.written.send(result);
+        }
+    });
+    queue
+}
+
+async fn send_controller_action(
+    client: &mut LightingControllerClient,
+    action: &ControllerAction,
+) -> Result<()> {
+    match action {
+        ControllerAction::Button { button_name, action } => match action {
+            ButtonActionType::Press => client.button_press(button_name).await,
+            ButtonActionType::Release => client.button_release(button_name).await,
+            ButtonActionType::Toggle => client.button_toggle(button_name).await,
+        },
+        ControllerAction::Bpm { bpm } => client.send_bpm(*bpm).await,
+        ControllerAction::Beat => client.send_beat().await,
+        // Fades run on their own task and never reach a queue
+        ControllerAction::Fade { .. } => Ok(()),
+        ControllerAction::FreezeOn => client.send_freeze_on().await,
+        ControllerAction::FreezeOff => client.send_freeze_off().await,
+        ControllerAction::AutoBpmOn => client.send_auto_bpm_on().await,
+        ControllerAction::AutoBpmOff => client.send_auto_bpm_off().await,
+    }
+}
+
+/// Input for the preset matcher task
+pub enum MatcherCommand {
+    /// A received MIDI message with the time it arrived
+    Midi(MidiMessage, Instant),
+    Timecode(Timecode, FrameRate),
+    UpdatePresets(Vec<Preset>),
+    SetCueListEnabled(bool),
+    SetGestureTimings(GestureTimings),
+}
+
+/// Matches incoming MIDI against preset triggers. Runs as its own task so
+/// gesture timers can fire while a note is still held.
 pub struct PresetMatcher {
     presets: Vec<Preset>,
+    rx: mpsc::UnboundedReceiver<MatcherCommand>,
     action_tx: mpsc::UnboundedSender<ActionCommand>,
+    events: EventSender,
+    cue_list_enabled: bool,
+    chaser: TimecodeChaser,
+    /// Above/below state of edge-triggered CC triggers, keyed by preset and trigger index
+    cc_edges: HashMap<(Uuid, usize), Option<bool>>,
+    /// Notes currently held down, keyed by channel and note, with the time they were pressed
+    held_notes: HashMap<(u8, u8), Instant>,
+    /// Progress of sequence triggers, keyed by preset and trigger index
+    sequences: HashMap<(Uuid, usize), SequenceProgress>,
+    gesture_timings: GestureTimings,
+    /// Gestures in progress, keyed by channel and note
+    gestures: HashMap<(u8, u8), GestureState>,
+    /// Momentary presets fired by a held note, released when it goes up
+    momentary: HashMap<(u8, u8), Vec<Preset>>,
 }
 
 impl PresetMatcher {
-    pub fn new(presets: Vec<Preset>, action_tx: mpsc::UnboundedSender<ActionCommand>) -> Self {
-        Self { presets, action_tx }
+    pub fn new(
+        rx: mpsc::UnboundedReceiver<MatcherCommand>,
+        action_tx: mpsc::UnboundedSender<ActionCommand>,
+        events: EventSender,
+    ) -> Self {
+        Self {
+            presets: Vec::new(),
+            rx,
+            action_tx,
+            events,
+            cue_list_enabled: false,
+            chaser: TimecodeChaser::new(),
+            cc_edges: HashMap::new(),
+            held_notes: HashMap::new(),
+            sequences: HashMap::new(),
+            gesture_timings: GestureTimings::default(),
+            gestures: HashMap::new(),
+            momentary: HashMap::new(),
+        }
+    }
+
+    pub async fn run(&mut self) {
+        loop {
+            let deadline = self.gestures.values().filter_map(|g| g.deadline).min();
+            let timer = async {
+                match deadline {
+                    Some(deadline) => tokio::time::sleep_until(deadline).await,
+                    None => std::future::pending().await,
+                }
+            };
+
+            tokio::select! {
+                cmd = self.rx.recv() => match cmd {
+                    Some(cmd) => self.handle_command(cmd),
+                    None => break,
+                },
+                _ = timer => self.handle_gesture_timers(Instant::now(), Local::now()),
+            }
+        }
+    }
+
+    fn handle_command(&mut self, cmd: MatcherCommand) {
+        match cmd {
+            MatcherCommand::Midi(msg, received) => self.handle_midi(&msg, received),
+            MatcherCommand::Timecode(timecode, rate) => self.handle_timecode(timecode, rate),
+            MatcherCommand::UpdatePresets(presets) => {
+                self.presets = presets;
+                self.cc_edges.clear();
+                self.sequences.clear();
+                self.gestures.clear();
+            }
+            MatcherCommand::SetCueListEnabled(enabled) => {
+                self.cue_list_enabled = enabled;
+                self.chaser.reset();
+            }
+            MatcherCommand::SetGestureTimings(timings) => self.gesture_timings = timings,
+        }
+    }
+
+    fn handle_midi(&mut self, msg: &MidiMessage, now: Instant) {
+        let received = wall_clock(now);
+        match msg {
+            MidiMessage::NoteOn(n) => {
+                self.held_notes.insert((n.channel, n.note), now);
+            }
+            MidiMessage::NoteOff(n) => {
+                self.held_notes.remove(&(n.channel, n.note));
+                self.release_momentary(msg, n.channel, n.note, received);
+            }
+            _ => {}
+        }
+        self.track_gesture(msg, now, received);
+
+        let mut fired: Option<Preset> = None;
+        for preset in &self.presets {
+            for (trigger_idx, trigger) in preset.triggers.iter().enumerate() {
+                if !trigger.matches(msg) {
+                    continue;
+                }
+
+                // Threshold and sequence state has to track every message, even after a preset fired
+                let hit = match (trigger, msg) {
+                    (
+                        MidiTrigger::ControlChange { threshold: Some(threshold), .. },
+                        MidiMessage::ControlChange { value, .. },
+                    ) => {
+                        let above = self.cc_edges.entry((preset.id, trigger_idx)).or_default();
+                        threshold.update(above, *value)
+                    }
+                    (
+                        MidiTrigger::Chord { notes, window_ms, .. },
+                        MidiMessage::NoteOn(n),
+                    ) => chord_held(&self.held_notes, n.channel, notes, *window_ms),
+                    (
+                        MidiTrigger::Sequence { notes, timeout_ms, .. },
+                        MidiMessage::NoteOn(n),
+                    ) => self
+                        .sequences
+                        .entry((preset.id, trigger_idx))
+                        .or_default()
+                        .advance(n.note, notes, Duration::from_millis(*timeout_ms as u64), now),
+                    // Gesture triggers fire from the gesture tracker instead
+                    (MidiTrigger::NoteOn { gesture: Some(_), .. }, _) => false,
+                    _ => true,
+                };
+
+                if hit && fired.is_none() {
+                    fired = fire(&self.action_tx, &self.events, preset, trigger, msg, received);
+                }
+            }
+        }
+
+        if let (Some(preset), MidiMessage::NoteOn(n)) = (fired, msg) {
+            self.hold_momentary(n.channel, n.note, preset);
+        }
+    }
+
+    fn hold_momentary(&mut self, channel: u8, note: u8, preset: Preset) {
+        if preset.momentary {
+            self.momentary.entry((channel, note)).or_default().push(preset);
+        }
+    }
+
+    /// Run the mirrored actions of momentary presets held by this note
+    fn release_momentary(&mut self, msg: &MidiMessage, channel: u8, note: u8, received: DateTime<Local>) {
+        for preset in self.momentary.remove(&(channel, note)).unwrap_or_default() {
+            let cause = format!("release of N{} Ch{}", note, channel);
+            let _ = self.events.send(ExecutorEvent::now(EventKind::PresetTriggered {
+                name: preset.name.clone(),
+                cause: cause.clone(),
+            }));
+            let trigger = TriggerSource {
+                cause,
+                message: Some(msg.display_name()),
+                received,
+            };
+            let _ = self.action_tx.send(ActionCommand::ExecutePreset(preset.mirrored(), trigger));
+        }
+    }
+
+    /// Follow presses and releases of notes used by gesture triggers
+    fn track_gesture(&mut self, msg: &MidiMessage, now: Instant, received: DateTime<Local>) {
+        let timings = self.gesture_timings;
+        match msg {
+            MidiMessage::NoteOn(n) => {
+                if !self.has_gesture_trigger(msg, None) {
+                    return;
+                }
+                let long_press = now + Duration::from_millis(timings.long_press_ms as u64);
+                match self.gestures.get_mut(&(n.channel, n.note)) {
+                    // Second press of a possible double tap
+                    Some(state) if state.taps == 1 => {
+                        state.held = true;
+                        state.deadline = Some(long_press);
+                    }
+                    _ => {
+                        self.gestures.insert(
+                            (n.channel, n.note),
+                            GestureState {
+                                press: msg.clone(),
+                                held: true,
+                                taps: 0,
+                                long_pressed: false,
+                                deadline: Some(long_press),
+                            },
+                        );
+                    }
+                }
+            }
+            MidiMessage::NoteOff(n) => {
+                let key = (n.channel, n.note);
+                let Some(state) = self.gestures.get_mut(&key) else {
+                    return;
+                };
+                if !state.held {
+                    return;
+                }
+                state.held = false;
+
+                if state.long_pressed {
+                    self.gestures.remove(&key);
+                } else if state.taps == 1 {
+                    let press = state.press.clone();
+                    self.gestures.remove(&key);
+                    self.fire_gesture(&press, NoteGesture::DoubleTap, received);
+                } else {
+                    let press = state.press.clone();
+                    // Only wait out the double tap window when a double tap could still fire
+                    if self.has_gesture_trigger(&press, Some(NoteGesture::DoubleTap)) {
+                        if let Some(state) = self.gestures.get_mut(&key) {
+                            state.taps = 1;
+                            state.deadline =
+                                Some(now + Duration::from_millis(timings.double_tap_ms as u64));
+                        }
+                    } else {
+                        self.gestures.remove(&key);
+                        self.fire_gesture(&press, NoteGesture::Tap, received);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Fire long presses that are still held and taps whose double tap window ran out
+    fn handle_gesture_timers(&mut self, now: Instant, received: DateTime<Local>) {
+        let due: Vec<(u8, u8)> = self
+            .gestures
+            .iter()
+            .filter(|(_, g)| g.deadline.is_some_and(|d| d <= now))
+            .map(|(key, _)| *key)
+            .collect();
+
+        for key in due {
+            let Some(state) = self.gestures.get_mut(&key) else {
+                continue;
+            };
+            let press = state.press.clone();
+            if state.held {
+                state.long_pressed = true;
+                state.deadline = None;
+                self.fire_gesture(&press, NoteGesture::LongPress, received);
+            } else {
+                self.gestures.remove(&key);
+                self.fire_gesture(&press, NoteGesture::Tap, received);
+            }
+        }
     }
 
-    pub fn update_presets(&mut self, presets: Vec<Preset>) {
-        self.presets = presets;
+    fn has_gesture_trigger(&self, press: &MidiMessage, wanted: Option<NoteGesture>) -> bool {
+        self.presets.iter().flat_map(|p| &p.triggers).any(|t| match t {
+            MidiTrigger::NoteOn { gesture: Some(g), .. } => {
+                t.matches(press) && (wanted.is_none() || wanted == Some(*g))
+            }
+            _ => false,
+        })
     }
 
-    pub fn handle_midi(&self, msg: &MidiMessage) -> Option<String> {
+    fn fire_gesture(&mut self, press: &MidiMessage, gesture: NoteGesture, received: DateTime<Local>) {
+        let mut fired: Option<Preset> = None;
         for preset in &self.presets {
-            for trigger in &preset.triggers {
-                if trigger.matches(msg) {
-                    let _ = self
-                        .action_tx
-                        .send(ActionCommand::ExecutePreset(preset.clone()));
-                    return Some(preset.name.clone()); // Return preset name for logging
+            let trigger = preset.triggers.iter().find(|t| {
+                matches!(t, MidiTrigger::NoteOn { gesture: Some(g), .. } if *g == gesture)
+                    && t.matches(press)
+            });
+            if let Some(trigger) = trigger {
+                fired = fire(&self.action_tx, &self.events, preset, trigger, press, received);
+                if fired.is_some() {
+                    break;
                 }
             }
         }
-        None
+
+        // Only a long press fires while the note is still down
+        if let (Some(preset), MidiMessage::NoteOn(n), NoteGesture::LongPress) = (fired, press, gesture) {
+            self.hold_momentary(n.channel, n.note, preset);
+        }
+    }
+
+    /// Fire every preset whose timecode stamp was crossed while playing forward
+    fn handle_timecode(&mut self, timecode: Timecode, rate: FrameRate) {
+        if !self.cue_list_enabled {
+            return;
+        }
+
+        let Some(window) = self.chaser.advance(timecode, rate) else {
+            return;
+        };
+
+        let mut cues: Vec<(u64, &Preset)> = self
+            .presets
+            .iter()
+            .filter_map(|p| Some((p.timecode?.to_frames(rate), p)))
+            .filter(|(frame, _)| window.contains(frame))
+            .collect();
+        cues.sort_by_key(|(frame, _)| *frame);
+
+        for (_, preset) in cues {
+            let cause = format!("Timecode {}", timecode);
+            let trigger = TriggerSource {
+                cause: cause.clone(),
+                message: None,
+                received: Local::now(),
+            };
+            let _ = self
+                .action_tx
+                .send(ActionCommand::ExecutePreset(preset.clone(), trigger));
+            let _ = self.events.send(ExecutorEvent::now(EventKind::PresetTriggered {
+                name: preset.name.clone(),
+                cause,
+            }));
+        }
+    }
+}
+
+/// Send a preset to the executor for a trigger hit, returning what was sent.
+/// Nothing fires when the trigger picks an action by position and the preset
+/// has no action there.
+fn fire(
+    action_tx: &mpsc::UnboundedSender<ActionCommand>,
+    events: &EventSender,
+    preset: &Preset,
+    trigger: &MidiTrigger,
+    msg: &MidiMessage,
+    received: DateTime<Local>,
+) -> Option<Preset> {
+    let mut preset = preset.clone();
+
+    // Range triggers can pick a single action by the note's position
+    if let Some(position) = trigger.action_position(msg) {
+        match preset.actions.get(position) {
+            Some(action) => preset.actions = vec![action.clone()],
+            None => return None,
+        }
+    }
+
+    let _ = events.send(ExecutorEvent::now(EventKind::PresetTriggered {
+        name: preset.name.clone(),
+        cause: trigger.display_name(),
+    }));
+    let source = TriggerSource {
+        cause: trigger.display_name(),
+        message: Some(msg.display_name()),
+        received,
+    };
+    let _ = action_tx.send(ActionCommand::ExecutePreset(preset.clone(), source));
+    Some(preset)
+}
+
+/// Wall clock time of an earlier `Instant`
+fn wall_clock(at: Instant) -> DateTime<Local> {
+    Local::now() - chrono::Duration::from_std(at.elapsed()).unwrap_or_default()
+}
+
+/// A tap, double tap or long press in progress on one note
+struct GestureState {
+    /// The NoteOn that started the gesture, matched against triggers when it fires
+    press: MidiMessage,
+    held: bool,
+    /// Completed taps waiting for a possible second one
+    taps: u8,
+    long_pressed: bool,
+    /// When the long press or the double tap window runs out
+    deadline: Option<Instant>,
+}
+
+/// Whether every chord note is held on `channel` and all were pressed within the window
+fn chord_held(held_notes: &HashMap<(u8, u8), Instant>, channel: u8, notes: &[u8], window_ms: u32) -> bool {
+    let pressed: Option<Vec<Instant>> = notes
+        .iter()
+        .map(|note| held_notes.get(&(channel, *note)).copied())
+        .collect();
+
+    match pressed.as_deref() {
+        Some(pressed) if !pressed.is_empty() => {
+            let first = pressed.iter().min().unwrap();
+            let last = pressed.iter().max().unwrap();
+            last.duration_since(*first) <= Duration::from_millis(window_ms as u64)
+        }
+        _ => false,
+    }
+}
+
+/// How far a sequence trigger has got through its notes
+#[derive(Default)]
+struct SequenceProgress {
+    matched: usize,
+    started: Option<Instant>,
+}
+
+impl SequenceProgress {
+    /// Feed the next note played, returning true when it completes the sequence
+    fn advance(&mut self, note: u8, notes: &[u8], timeout: Duration, now: Instant) -> bool {
+        if let Some(started) = self.started {
+            if now.duration_since(started) > timeout {
+                self.matched = 0;
+            }
+        }
+
+        if notes.get(self.matched) == Some(&note) {
+            if self.matched == 0 {
+                self.started = Some(now);
+            }
+            self.matched += 1;
+        } else if notes.first() == Some(&note) {
+            // A wrong note that opens the sequence starts a fresh attempt
+            self.matched = 1;
+            self.started = Some(now);
+        } else {
+            self.matched = 0;
+            self.started = None;
+        }
+
+        if !notes.is_empty() && self.matched == notes.len() {
+            *self = Self::default();
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grouped(name: &str) -> Preset {
+        let mut preset = Preset::new(name.to_string(), String::new());
+        // Long enough to still be running when the next preset starts
+        preset.delay_secs = 60.0;
+        preset.group = Some("looks".to_string());
+        preset
+    }
+
+    #[tokio::test]
+    async fn mirrored_release_leaves_its_group_running() {
+        let (events_tx, mut events_rx) = mpsc::unbounded_channel();
+        let (_tx, rx) = mpsc::unbounded_channel();
+        let mut executor = ActionExecutor::new(rx, events_tx, SharedTempo::default());
+        let first = grouped("First");
+        let second = grouped("Second");
+
+        executor.start_preset(first.clone(), TriggerSource::manual("test"));
+        executor.start_preset(second.clone(), TriggerSource::manual("test"));
+        executor.start_preset(first.mirrored(), TriggerSource::manual("release"));
+
+        assert!(executor.running.contains_key(&second.id));
+        while let Ok(event) = events_rx.try_recv() {
+            if let EventKind::PresetFinished { preset_id, .. } = event.kind {
+                assert_ne!(preset_id, second.id, "release cancelled another preset of the group");
+            }
+        }
     }
 }
@@ -0,0 +1,266 @@
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+use crate::action_executor::{EventKind, ExecutorEvent, PresetOutcome};
+
+/// Executions kept in memory and in the history file
+pub const MAX_HISTORY_ENTRIES: usize = 1000;
+
+/// What fired a preset execution
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TriggerSource {
+    /// Trigger display name, or how the preset was fired by hand
+    pub cause: String,
+    /// The MIDI message that matched, when fired from MIDI
+    pub message: Option<String>,
+    /// When the triggering message arrived
+    pub received: DateTime<Local>,
+}
+
+impl TriggerSource {
+    pub fn manual(cause: &str) -> Self {
+        Self {
+            cause: cause.to_string(),
+            message: None,
+            received: Local::now(),
+        }
+    }
+}
+
+/// One command of an execution, with when it was written to the controller
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActionRecord {
+    pub action: String,
+    /// When the command was written, `None` if it failed
+    pub written: Option<DateTime<Local>>,
+    pub error: Option<String>,
+}
+
+/// A finished preset execution
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub preset_id: Uuid,
+    pub preset_name: String,
+    pub trigger: TriggerSource,
+    /// When the execution started, after waiting for any queued run before it
+    pub started: DateTime<Local>,
+    pub actions: Vec<ActionRecord>,
+    pub finished: DateTime<Local>,
+    pub outcome: PresetOutcome,
+}
+
+impl HistoryEntry {
+    /// Milliseconds from the trigger arriving to `at`
+    pub fn ms_after_trigger(&self, at: DateTime<Local>) -> i64 {
+        (at - self.trigger.received).num_milliseconds()
+    }
+
+    /// Milliseconds from the trigger arriving to the last command written
+    pub fn duration_ms(&self) -> i64 {
+        let last = self
+            .actions
+            .iter()
+            .filter_map(|a| a.written)
+            .max()
+            .unwrap_or(self.started);
+        self.ms_after_trigger(last)
+    }
+}
+
+/// Which outcomes the history panel shows
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OutcomeFilter {
+    #[default]
+    All,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl OutcomeFilter {
+    pub const ALL: [OutcomeFilter; 4] = [
+        OutcomeFilter::All,
+        OutcomeFilter::Completed,
+        OutcomeFilter::Failed,
+        OutcomeFilter::Cancelled,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            OutcomeFilter::All => "All",
+            OutcomeFilter::Completed => "Completed",
+            OutcomeFilter::Failed => "Failed",
+            OutcomeFilter::Cancelled => "Cancelled",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct HistoryFilter {
+    /// Case-insensitive match on the preset name or trigger
+    pub text: String,
+    pub outcome: OutcomeFilter,
+    /// Only executions whose last command went out at least this long after the trigger
+    pub min_duration_ms: i32,
+}
+
+impl HistoryFilter {
+    pub fn matches(&self, entry: &HistoryEntry) -> bool {
+        let text = self.text.to_lowercase();
+        let text_matches = text.is_empty()
+            || entry.preset_name.to_lowercase().contains(&text)
+            || entry.trigger.cause.to_lowercase().contains(&text)
+            || entry
+                .trigger
+                .message
+                .as_ref()
+                .is_some_and(|m| m.to_lowercase().contains(&text));
+
+        let outcome_matches = match self.outcome {
+            OutcomeFilter::All => true,
+            OutcomeFilter::Completed => entry.outcome == PresetOutcome::Completed,
+            OutcomeFilter::Failed => matches!(entry.outcome, PresetOutcome::Failed(_)),
+            OutcomeFilter::Cancelled => entry.outcome == PresetOutcome::Cancelled,
+        };
+
+        text_matches && outcome_matches && entry.duration_ms() >= self.min_duration_ms as i64
+    }
+}
+
+/// Bounded history of preset executions, built from executor events and
+/// persisted as JSON lines. The file is appended to as executions finish and
+/// rewritten with only the newest entries once it grows to twice the limit.
+pub struct ExecutionHistory {
+    path: PathBuf,
+    max_entries: usize,
+    entries: VecDeque<HistoryEntry>,
+    /// Executions still running, keyed by execution id
+    open: HashMap<u64, HistoryEntry>,
+    /// Lines currently in the file
+    file_lines: usize,
+}
+
+impl ExecutionHistory {
+    /// A history that starts out empty, writing to `path` as executions finish
+    pub fn empty(path: PathBuf, max_entries: usize) -> Self {
+        Self {
+            path,
+            max_entries,
+            entries: VecDeque::new(),
+            open: HashMap::new(),
+            file_lines: 0,
+        }
+    }
+
+    pub fn load(path: PathBuf, max_entries: usize) -> Result<Self> {
+        let mut history = Self::empty(path, max_entries);
+        if history.path.exists() {
+            // Read lossily so a corrupted byte only costs the line it is on
+            let data = fs::read(&history.path)?;
+            for line in String::from_utf8_lossy(&data).lines() {
+                history.file_lines += 1;
+                // Skip lines that don't parse rather than losing the whole history
+                if let Ok(entry) = serde_json::from_str::<HistoryEntry>(line) {
+                    history.entries.push_back(entry);
+                }
+            }
+        }
+        while history.entries.len() > max_entries {
+            history.entries.pop_front();
+        }
+        Ok(history)
+    }
+
+    /// Oldest first
+    pub fn entries(&self) -> &VecDeque<HistoryEntry> {
+        &self.entries
+    }
+
+    /// Follow an executor event, saving the execution once it finishes
+    pub fn record(&mut self, event: &ExecutorEvent) -> Result<()> {
+        match &event.kind {
+            EventKind::PresetStarted { execution, preset_id, name, trigger } => {
+                self.open.insert(
+                    *execution,
+                    HistoryEntry {
+                        preset_id: *preset_id,
+                        preset_name: name.clone(),
+                        trigger: trigger.clone(),
+                        started: event.at,
+                        actions: Vec::new(),
+                        finished: event.at,
+                        outcome: PresetOutcome::Completed,
+                    },
+                );
+            }
+            EventKind::ActionSent { execution: Some(execution), action, .. } => {
+                if let Some(entry) = self.open.get_mut(execution) {
+                    entry.actions.push(ActionRecord {
+                        action: action.clone(),
+                        written: Some(event.at),
+                        error: None,
+                    });
+                }
+            }
+            EventKind::ActionFailed { execution: Some(execution), action, error, .. } => {
+                if let Some(entry) = self.open.get_mut(execution) {
+                    entry.actions.push(ActionRecord {
+                        action: action.clone(),
+                        written: None,
+                        error: Some(error.clone()),
+                    });
+                }
+            }
+            EventKind::PresetFinished { execution, outcome, .. } => {
+                // Executions cancelled while still queued never started and aren't recorded
+                if let Some(mut entry) = self.open.remove(execution) {
+                    entry.finished = event.at;
+                    entry.outcome = outcome.clone();
+                    self.push(entry)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, entry: HistoryEntry) -> Result<()> {
+        let line = serde_json::to_string(&entry)?;
+        self.entries.push_back(entry);
+        if self.entries.len() > self.max_entries {
+            self.entries.pop_front();
+        }
+
+        if self.file_lines >= self.max_entries * 2 {
+            self.rewrite()
+        } else {
+            let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+            writeln!(file, "{}", line)?;
+            self.file_lines += 1;
+            Ok(())
+        }
+    }
+
+    pub fn clear(&mut self) -> Result<()> {
+        self.entries.clear();
+        self.rewrite()
+    }
+
+    /// Replace the file with the entries kept in memory
+    fn rewrite(&mut self) -> Result<()> {
+        let mut data = String::new();
+        for entry in &self.entries {
+            data.push_str(&serde_json::to_string(entry)?);
+            data.push('\n');
+        }
+        fs::write(&self.path, data)?;
+        self.file_lines = self.entries.len();
+        Ok(())
+    }
+}
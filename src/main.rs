@@ -1,21 +1,33 @@
 #![cfg_attr(target_os = "windows", windows_subsystem = "windows")]
 
 mod action_executor;
+mod history;
+mod midi_decoder;
 mod models;
 mod persistence;
 mod tcp_client;
+mod tempo;
+mod timecode;
 mod versioning;
 mod versioned_data;
 
-use action_executor::{ActionCommand, ActionExecutor, PresetMatcher};
+use action_executor::{
+    ActionCommand, ActionExecutor, EventKind, ExecutorEvent, LinkState, MatcherCommand,
+    PresetMatcher, PresetOutcome,
+};
 use anyhow::Result;
-use chrono::Local;
+use chrono::{DateTime, Local};
+use history::{ExecutionHistory, HistoryEntry, HistoryFilter, OutcomeFilter, TriggerSource, MAX_HISTORY_ENTRIES};
 use imgui::*;
+use midi_decoder::HighResDecoder;
 use models::*;
 use persistence::{AppConfig, PresetStorage};
 use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
+use tcp_client::LiveMessage;
+use tempo::{ClockEvent, MidiClockTracker, SharedTempo, TapTempo};
+use timecode::{FrameRate, MtcDecoder, Timecode};
 use tokio::sync::mpsc;
 use midir::MidiInputConnection;
 
@@ -24,9 +36,36 @@ enum ConnectionState {
     Disconnected,
     Connecting,
     Connected,
+    /// Waiting to retry after the connection failed or dropped
+    BackingOff {
+        attempt: u32,
+        retry_at: DateTime<Local>,
+        error: String,
+    },
+    /// Reconnecting gave up after too many failed attempts
     Error(String),
 }
 
+/// How long MIDI learn waits for the rest of a 14-bit, NRPN or RPN message
+const LEARN_COMBINE_WINDOW: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// What a captured MIDI Learn trigger will be assigned to
+#[derive(Clone, Copy, PartialEq)]
+enum LearnTarget {
+    TapTempo,
+    /// Add the captured trigger to the preset at this index
+    PresetTrigger(usize),
+    /// Create a fader mapping from the captured CC
+    FaderMapping,
+}
+
+/// Latest execution state of a preset, shown as a badge next to its name
+struct PresetStatus {
+    /// `None` while the preset is running
+    outcome: Option<PresetOutcome>,
+    at: DateTime<Local>,
+}
+
 struct MidiLog {
     entries: Vec<(String, String)>,
     max_entries: usize,
@@ -41,7 +80,12 @@ impl MidiLog {
     }
 
     fn add(&mut self, message: String) {
-        let timestamp = Local::now().format("%H:%M:%S%.3f").to_string();
+        self.add_at(Local::now(), message);
+    }
+
+    /// Log a message with the time it happened rather than when it was received
+    fn add_at(&mut self, at: DateTime<Local>, message: String) {
+        let timestamp = at.format("%H:%M:%S%.3f").to_string();
         self.entries.push((timestamp, message));
         if self.entries.len() > self.max_entries {
             self.entries.remove(0);
@@ -55,12 +99,34 @@ struct AppState {
     buttons: Vec<Button>,
     midi_log: MidiLog,
     midi_messages: HashMap<String, Vec<MidiMessage>>,
-    flashing_messages: HashMap<String, f64>, // Maps display name to flash start time
+    flashing_messages: HashMap<String, f64>, // Maps monitor key to flash start time
     midi_learn: MidiLearnState,
+    /// Raw CC held back during learn in case it is part of a high resolution message
+    learn_candidate: Option<(MidiMessage, std::time::Instant)>,
+    learn_target: Option<LearnTarget>,
     storage: PresetStorage,
     config: AppConfig,
     action_tx: mpsc::UnboundedSender<ActionCommand>,
-    preset_matcher: Arc<Mutex<PresetMatcher>>,
+    matcher_tx: mpsc::UnboundedSender<MatcherCommand>,
+    high_res_decoder: HighResDecoder,
+    mtc_decoder: MtcDecoder,
+    current_timecode: Option<(Timecode, FrameRate)>,
+    midi_clock: MidiClockTracker,
+    last_sent_bpm: Option<f32>,
+    tap_tempo: TapTempo,
+    tap_bpm: Option<f32>,
+    /// Last value sent per fader, so unchanged values aren't resent
+    last_fader_values: HashMap<u32, i32>,
+    preset_status: HashMap<uuid::Uuid, PresetStatus>,
+    history: ExecutionHistory,
+    history_filter: HistoryFilter,
+    show_history_modal: bool,
+    /// Round trip of the last answered heartbeat
+    heartbeat_rtt: Option<std::time::Duration>,
+    /// Heartbeats in a row that went unanswered
+    heartbeat_misses: u32,
+    /// Tempo used to answer BPM requests from the controller
+    tempo: SharedTempo,
     
     // MIDI Port Selection
     available_midi_ports: Vec<String>,
@@ -82,8 +148,17 @@ struct AppState {
     edit_preset_name: String,
     edit_preset_desc: String,
     edit_preset_delay: f32,
+    edit_preset_group: String,
     show_edit_preset_modal: bool,
     pending_edit_preset: Option<usize>,
+    edit_trigger: Option<MidiTrigger>,
+    show_edit_trigger_modal: bool,
+    pending_edit_trigger: Option<(usize, usize)>,
+    edit_fade: Option<ControllerAction>,
+    show_edit_fade_modal: bool,
+    pending_edit_fade: Option<(usize, usize)>,
+    sysex_pattern_input: String,
+    timecode_cue_input: String,
     pending_button_action: Option<(u32, String)>,
     last_action_type: ButtonActionType,
     action_delay: f32,
@@ -102,16 +177,25 @@ impl AppState {
     fn new(
         storage: PresetStorage,
         action_tx: mpsc::UnboundedSender<ActionCommand>,
+        matcher_tx: mpsc::UnboundedSender<MatcherCommand>,
         available_midi_ports: Vec<String>,
         midi_connection: Arc<Mutex<Option<MidiInputConnection<()>>>>,
+        tempo: SharedTempo,
     ) -> Result<Self> {
         let presets = storage.load().unwrap_or_default();
         let config = storage.load_config().unwrap_or_default();
+        // A broken history file shouldn't keep the app from starting
+        let history = ExecutionHistory::load(storage.history_path(), MAX_HISTORY_ENTRIES)
+            .unwrap_or_else(|e| {
+                eprintln!("Failed to load execution history: {}", e);
+                ExecutionHistory::empty(storage.history_path(), MAX_HISTORY_ENTRIES)
+            });
         
-        let preset_matcher = Arc::new(Mutex::new(PresetMatcher::new(
-            presets.clone(),
-            action_tx.clone(),
-        )));
+        let _ = matcher_tx.send(MatcherCommand::UpdatePresets(presets.clone()));
+        let _ = matcher_tx.send(MatcherCommand::SetCueListEnabled(config.timecode_cue_list));
+        let _ = matcher_tx.send(MatcherCommand::SetGestureTimings(config.gesture_timings));
+        let _ = action_tx.send(ActionCommand::SetHeartbeat(config.heartbeat));
+        tempo.set_settings(config.tempo);
 
         // Find the last used MIDI port
         let selected_midi_port = if let Some(ref last_port) = config.last_midi_port {
@@ -144,10 +228,27 @@ impl AppState {
             midi_messages: HashMap::new(),
             flashing_messages: HashMap::new(),
             midi_learn: MidiLearnState::new(),
+            learn_candidate: None,
+            learn_target: None,
             storage,
             config,
             action_tx,
-            preset_matcher,
+            matcher_tx,
+            high_res_decoder: HighResDecoder::new(),
+            mtc_decoder: MtcDecoder::new(),
+            current_timecode: None,
+            midi_clock: MidiClockTracker::new(),
+            last_sent_bpm: None,
+            tap_tempo: TapTempo::new(),
+            tap_bpm: None,
+            last_fader_values: HashMap::new(),
+            preset_status: HashMap::new(),
+            history,
+            history_filter: HistoryFilter::default(),
+            show_history_modal: false,
+            heartbeat_rtt: None,
+            heartbeat_misses: 0,
+            tempo,
             available_midi_ports,
             selected_midi_port,
             midi_connection_active: false,
@@ -163,8 +264,17 @@ impl AppState {
             edit_preset_name: String::new(),
             edit_preset_desc: String::new(),
             edit_preset_delay: 0.1,
+            edit_preset_group: String::new(),
             show_edit_preset_modal: false,
             pending_edit_preset: None,
+            edit_trigger: None,
+            show_edit_trigger_modal: false,
+            pending_edit_trigger: None,
+            edit_fade: None,
+            show_edit_fade_modal: false,
+            pending_edit_fade: None,
+            sysex_pattern_input: String::new(),
+            timecode_cue_input: String::new(),
             pending_button_action: None,
             last_action_type,
             action_delay: 0.0,
@@ -178,9 +288,7 @@ impl AppState {
 
     fn save_presets(&mut self) -> Result<()> {
         self.storage.save(&self.presets)?;
-        if let Ok(mut matcher) = self.preset_matcher.lock() {
-            matcher.update_presets(self.presets.clone());
-        }
+        let _ = self.matcher_tx.send(MatcherCommand::UpdatePresets(self.presets.clone()));
         Ok(())
     }
 
@@ -224,6 +332,10 @@ impl AppState {
             triggers: original.triggers.clone(),
             actions: original.actions.clone(),
             delay_secs: original.delay_secs,
+            timecode: original.timecode,
+            momentary: original.momentary,
+            retrigger: original.retrigger,
+            group: original.group.clone(),
         };
 
         self.presets.push(duplicate);
@@ -267,45 +379,500 @@ impl AppState {
         }
     }
 
-    fn handle_midi_message(&mut self, msg: MidiMessage) {
+    /// Handle a message that arrived from the MIDI port at `received`
+    fn handle_midi_message(&mut self, msg: MidiMessage, received: std::time::Instant) {
+        // 14-bit CC and NRPN/RPN values are assembled alongside the raw CCs,
+        // decoded first so learn can take the combined message over its parts
+        let combined = self.high_res_decoder.feed(&msg, received);
+        self.process_midi_message(msg, received);
+        if let Some(combined) = combined {
+            self.process_midi_message(combined, received);
+        }
+    }
+
+    fn process_midi_message(&mut self, msg: MidiMessage, received: std::time::Instant) {
+        if let Some((timecode, rate)) = self.mtc_decoder.feed(&msg) {
+            self.current_timecode = Some((timecode, rate));
+            let _ = self.matcher_tx.send(MatcherCommand::Timecode(timecode, rate));
+        }
+
+        if let Some(event) = self.midi_clock.feed(&msg, received) {
+            self.handle_clock_event(event);
+        }
+
+        // Quarter frames and clock pulses arrive continuously; keep them out of the log and tree
+        if msg.is_timing() {
+            return;
+        }
+
         // Clone early for storage, keep original for other uses
         let msg_for_storage = msg.clone();
         let display = msg.display_name();
         self.midi_log.add(format!("{}", display));
 
-        let category = match &msg {
-            MidiMessage::NoteOn(_) => "Note On",
-            MidiMessage::NoteOff(_) => "Note Off",
-            MidiMessage::ControlChange { .. } => "Control Change",
-        };
+        let category = msg.category();
         
         let messages = self.midi_messages
             .entry(category.to_string())
             .or_insert_with(Vec::new);
         
-        // Only add if it doesn't already exist (check by display name)
-        let display_str = display.clone();
-        let already_exists = messages.iter().any(|m| m.display_name() == display_str);
-        if !already_exists {
-            messages.push(msg_for_storage);
-        } else {
-            // Flash the existing entry
-            let current_time = std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_secs_f64();
-            self.flashing_messages.insert(display_str.clone(), current_time);
+        // One entry per note, controller or parameter, showing its latest value
+        let key = msg.monitor_key();
+        match messages.iter_mut().find(|m| m.monitor_key() == key) {
+            None => messages.push(msg_for_storage),
+            Some(existing) => {
+                *existing = msg_for_storage;
+                // Flash the existing entry
+                let current_time = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs_f64();
+                self.flashing_messages.insert(key, current_time);
+            }
+        }
+
+        if self.midi_learn.active {
+            self.learn_from(msg);
+            return;
+        }
+
+        // The tap tempo binding is dedicated and doesn't also fire presets
+        if self.config.tap_tempo_trigger.as_ref().is_some_and(|t| t.matches(&msg)) {
+            self.handle_tap(received);
+            return;
+        }
+
+        self.apply_fader_mappings(&msg);
+
+        let _ = self
+            .matcher_tx
+            .send(MatcherCommand::Midi(msg, tokio::time::Instant::from_std(received)));
+    }
+
+    fn apply_learned_trigger(&mut self, trigger: MidiTrigger) {
+        match self.learn_target.take() {
+            Some(LearnTarget::TapTempo) => {
+                self.midi_log.add(format!("Tap tempo bound to {}", trigger.display_name()));
+                self.config.tap_tempo_trigger = Some(trigger);
+                self.save_config();
+            }
+            Some(LearnTarget::FaderMapping) => {
+                let mapping = match trigger {
+                    MidiTrigger::ControlChange { channel, cc, .. } => FaderMapping::new(channel, cc, false),
+                    MidiTrigger::Cc14 { channel, cc, .. } => FaderMapping::new(channel, cc, true),
+                    other => {
+                        self.midi_log.add(format!("Fader mappings need a CC, got {}", other.display_name()));
+                        return;
+                    }
+                };
+                self.midi_log.add(format!("Learned fader mapping: {}", mapping.display_name()));
+                self.config.fader_mappings.push(mapping);
+                self.save_config();
+            }
+            Some(LearnTarget::PresetTrigger(preset_idx)) => {
+                let Some(preset) = self.presets.get_mut(preset_idx) else {
+                    return;
+                };
+                if preset.triggers.contains(&trigger) {
+                    return;
+                }
+
+                self.midi_log.add(format!("Learned trigger: {}", trigger.display_name()));
+                preset.triggers.push(trigger.clone());
+                let trigger_idx = preset.triggers.len() - 1;
+                let _ = self.save_presets();
+
+                // Offer to widen a learned note to a range right away
+                if matches!(trigger, MidiTrigger::NoteOn { .. }) {
+                    self.edit_trigger = Some(trigger);
+                    self.pending_edit_trigger = Some((preset_idx, trigger_idx));
+                    self.show_edit_trigger_modal = true;
+                }
+            }
+            None => {}
         }
+    }
+
+    fn handle_executor_event(&mut self, event: ExecutorEvent) {
+        if let Err(e) = self.history.record(&event) {
+            self.midi_log.add(format!("Failed to save execution history: {}", e));
+        }
+
+        let at = event.at;
+        match event.kind {
+            EventKind::ButtonsLoaded(buttons) => {
+                let button_count = buttons.len();
+                // Only mark as updated if the list actually changed
+                let buttons_changed = self.buttons != buttons;
+                if buttons_changed {
+                    self.buttons_just_updated = true;
+                }
+                self.buttons = buttons;
+                if buttons_changed {
+                    self.midi_log.add_at(at, format!("Loaded {} buttons", button_count));
+                }
+            }
+            EventKind::Link(link) => self.handle_link_state(at, link),
+            EventKind::Disconnected => {
+                if !self.buttons.is_empty() {
+                    self.buttons_just_updated = true;
+                }
+                self.buttons.clear();
+                self.connection_state = ConnectionState::Disconnected;
+            }
+            EventKind::PresetTriggered { name, cause } => {
+                self.midi_log.add_at(at, format!("Executing preset: {} ({})", name, cause));
+            }
+            EventKind::PresetStarted { preset_id, name, .. } => {
+                self.midi_log.add_at(at, format!("Preset started: {}", name));
+                self.preset_status.insert(preset_id, PresetStatus { outcome: None, at });
+            }
+            EventKind::ActionSent { preset_id, action, .. } => {
+                self.midi_log.add_at(at, format!("Sent: {}{}", action, self.preset_suffix(preset_id)));
+            }
+            EventKind::ActionFailed { preset_id, action, error, .. } => {
+                self.midi_log.add_at(
+                    at,
+                    format!("Failed: {}{}: {}", action, self.preset_suffix(preset_id), error),
+                );
+            }
+            EventKind::PresetFinished { preset_id, name, outcome, .. } => {
+                self.midi_log.add_at(at, format!("Preset finished: {} ({})", name, outcome.name()));
+                self.preset_status.insert(
+                    preset_id,
+                    PresetStatus {
+                        outcome: Some(outcome),
+                        at,
+                    },
+                );
+            }
+            EventKind::CommandFailed(err) => {
+                self.midi_log.add_at(at, format!("Error: {}", err));
+            }
+            EventKind::Heartbeat { rtt, misses } => {
+                if rtt.is_none() {
+                    self.midi_log.add_at(
+                        at,
+                        format!("Heartbeat missed ({}/{})", misses, self.config.heartbeat.max_misses),
+                    );
+                }
+                self.heartbeat_rtt = rtt.or(self.heartbeat_rtt);
+                self.heartbeat_misses = misses;
+            }
+            EventKind::ControllerMessage(msg) => self.handle_controller_message(at, msg),
+        }
+    }
+
+    fn handle_link_state(&mut self, at: DateTime<Local>, link: LinkState) {
+        match link {
+            LinkState::Connecting { attempt } => {
+                if attempt > 1 {
+                    self.midi_log.add_at(at, format!("Reconnecting to {} (attempt {})", self.connection_address, attempt));
+                }
+                self.connection_state = ConnectionState::Connecting;
+            }
+            LinkState::Connected => {
+                self.midi_log.add_at(at, "Connected!".to_string());
+                self.heartbeat_rtt = None;
+                self.heartbeat_misses = 0;
+                self.connection_state = ConnectionState::Connected;
+            }
+            LinkState::BackingOff { attempt, retry_at, error } => {
+                let secs = (retry_at - at).num_milliseconds() as f32 / 1000.0;
+                self.midi_log.add_at(at, format!("Connection error: {} (retrying in {:.1}s)", error, secs));
+                self.connection_state = ConnectionState::BackingOff { attempt, retry_at, error };
+            }
+            LinkState::GaveUp(err) => {
+                self.midi_log.add_at(at, format!("Gave up reconnecting: {}", err));
+                self.connection_state = ConnectionState::Error(err);
+            }
+        }
+    }
+
+    /// Log what happens on the controller itself; replies and beats are left out
+    fn handle_controller_message(&mut self, at: DateTime<Local>, msg: LiveMessage) {
+        match msg {
+            LiveMessage::ButtonPress(name) => {
+                self.midi_log.add_at(at, format!("Controller: {} pressed", name));
+            }
+            LiveMessage::ButtonRelease(name) => {
+                self.midi_log.add_at(at, format!("Controller: {} released", name));
+            }
+            LiveMessage::FaderChange { index, value } => {
+                // Mapped faders resend once the MIDI value differs from where the controller put them
+                self.last_fader_values.insert(index, value);
+                self.midi_log.add_at(at, format!("Controller: Fader {} = {}", index, value));
+            }
+            LiveMessage::InterfaceChange(data) => {
+                self.midi_log.add_at(at, format!("Controller: interface changed {}", data));
+            }
+            LiveMessage::Error(err) => {
+                self.midi_log.add_at(at, format!("Controller error: {}", err));
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether the controller answers heartbeats, and how quickly
+    fn render_link_health(&self, ui: &Ui) {
+        match (self.heartbeat_misses, self.heartbeat_rtt) {
+            (0, Some(rtt)) => ui.text_colored(
+                [0.4, 1.0, 0.4, 1.0],
+                format!("Responding ({:.1} ms)", rtt.as_secs_f32() * 1000.0),
+            ),
+            (0, None) => ui.text_disabled("Waiting for first heartbeat..."),
+            (misses, _) => ui.text_colored(
+                [1.0, 0.8, 0.2, 1.0],
+                format!("Not responding ({}/{} missed)", misses, self.config.heartbeat.max_misses),
+            ),
+        }
+        if ui.is_item_hovered() {
+            ui.tooltip_text("Round trip of the last heartbeat answered by the controller");
+        }
+    }
+
+    fn render_tempo_settings(&mut self, ui: &Ui) {
+        let mut settings = self.config.tempo;
+        let mut changed = false;
+
+        let reading = |source: TempoSource| match self.tempo.reading(source) {
+            Some(bpm) => format!("{} ({:.1})", source.name(), bpm),
+            None => format!("{} (--)", source.name()),
+        };
+        ui.set_next_item_width(150.0);
+        if let Some(_token) = ui.begin_combo("Source##tempo", reading(settings.source)) {
+            for source in TempoSource::ALL {
+                let selected = settings.source == source;
+                if ui.selectable_config(reading(source)).selected(selected).build() && !selected {
+                    settings.source = source;
+                    changed = true;
+                }
+            }
+        }
+        if ui.is_item_hovered() {
+            ui.tooltip_text("Where the BPM sent back to ShowXpress comes from; sources without a reading yet use the manual BPM");
+        }
+
+        ui.set_next_item_width(150.0);
+        if ui.input_float("Manual BPM##tempo", &mut settings.manual_bpm).build() {
+            settings.manual_bpm = settings.manual_bpm.clamp(1.0, 999.0);
+            changed = true;
+        }
+
+        if changed {
+            self.config.tempo = settings;
+            self.tempo.set_settings(settings);
+            self.save_config();
+        }
+
+        let is_connected = self.connection_state == ConnectionState::Connected;
+        ui.disabled(!is_connected, || {
+            if ui.button("Send BPM##tempo") {
+                let _ = self.action_tx.send(ActionCommand::SendBpm(self.tempo.bpm()));
+            }
+        });
+    }
+
+    fn render_heartbeat_settings(&mut self, ui: &Ui) {
+        let mut heartbeat = self.config.heartbeat;
+        ui.set_next_item_width(150.0);
+        let interval_changed = ui.slider("Interval (ms)", 500, 10000, &mut heartbeat.interval_ms);
+        if ui.is_item_hovered() {
+            ui.tooltip_text("Time between heartbeats");
+        }
+        ui.set_next_item_width(150.0);
+        let timeout_changed = ui.slider("Timeout (ms)", 100, 5000, &mut heartbeat.timeout_ms);
+        if ui.is_item_hovered() {
+            ui.tooltip_text("How long the controller has to answer a heartbeat");
+        }
+        ui.set_next_item_width(150.0);
+        let misses_changed = ui.slider("Misses", 1, 10, &mut heartbeat.max_misses);
+        if ui.is_item_hovered() {
+            ui.tooltip_text("Unanswered heartbeats in a row before the link is treated as dead and reconnected");
+        }
+        if interval_changed || timeout_changed || misses_changed {
+            self.config.heartbeat = heartbeat;
+            self.save_config();
+            let _ = self.action_tx.send(ActionCommand::SetHeartbeat(heartbeat));
+        }
+    }
+
+    fn render_history(&mut self, ui: &Ui) {
+        let filter = &mut self.history_filter;
+        ui.set_next_item_width(200.0);
+        ui.input_text("Search##history", &mut filter.text).build();
+        if ui.is_item_hovered() {
+            ui.tooltip_text("Match the preset name, trigger or MIDI message");
+        }
+        ui.same_line();
+        ui.set_next_item_width(100.0);
+        if let Some(_token) = ui.begin_combo("Outcome##history", filter.outcome.name()) {
+            for outcome in OutcomeFilter::ALL {
+                if ui.selectable_config(outcome.name()).selected(filter.outcome == outcome).build() {
+                    filter.outcome = outcome;
+                }
+            }
+        }
+        ui.same_line();
+        ui.set_next_item_width(80.0);
+        if ui.input_int("Slower than (ms)##history", &mut filter.min_duration_ms).build() {
+            filter.min_duration_ms = filter.min_duration_ms.max(0);
+        }
+        if ui.is_item_hovered() {
+            ui.tooltip_text("Only show executions whose last command went out at least this long after the trigger");
+        }
+
+        ui.child_window("##history_entries")
+            .size([700.0, 400.0])
+            .border(true)
+            .build(|| {
+                let entries: Vec<(usize, &HistoryEntry)> = self
+                    .history
+                    .entries()
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .filter(|(_, entry)| self.history_filter.matches(entry))
+                    .collect();
+                if entries.is_empty() {
+                    ui.text_disabled("No executions recorded");
+                }
+                for (i, entry) in entries {
+                    render_history_entry(ui, i, entry);
+                }
+            });
+    }
+
+    /// " (preset name)" for log lines about a preset's actions
+    fn preset_suffix(&self, preset_id: Option<uuid::Uuid>) -> String {
+        preset_id
+            .and_then(|id| self.presets.iter().find(|p| p.id == id))
+            .map(|p| format!(" ({})", p.name))
+            .unwrap_or_default()
+    }
+
+    /// Send mapped fader values straight to the executor, skipping the matcher
+    fn apply_fader_mappings(&mut self, msg: &MidiMessage) {
+        if self.connection_state != ConnectionState::Connected {
+            return;
+        }
+        for mapping in &self.config.fader_mappings {
+            let Some(value) = mapping.value_for(msg) else {
+                continue;
+            };
+            if self.last_fader_values.insert(mapping.fader, value) != Some(value) {
+                let _ = self.action_tx.send(ActionCommand::SetFader {
+                    index: mapping.fader,
+                    value,
+                });
+            }
+        }
+    }
+
+    fn start_learn(&mut self, target: LearnTarget) {
+        self.learn_target = Some(target);
+        self.midi_learn.active = true;
+        self.midi_learn.captured = None;
+    }
+
+    fn cancel_learn(&mut self) {
+        self.learn_target = None;
+        self.midi_learn.active = false;
+        self.learn_candidate = None;
+    }
+
+    /// Capture a message for MIDI learn. Raw CCs that may belong to a 14-bit,
+    /// NRPN or RPN message are held back briefly, and the combined message
+    /// wins if it follows.
+    fn learn_from(&mut self, msg: MidiMessage) {
+        let msg = if midi_decoder::may_combine(&msg) {
+            self.learn_candidate
+                .get_or_insert_with(|| (msg, std::time::Instant::now()));
+            return;
+        } else if matches!(msg, MidiMessage::Cc14 { .. } | MidiMessage::Nrpn { .. } | MidiMessage::Rpn { .. }) {
+            self.learn_candidate = None;
+            msg
+        } else {
+            // Something else arrived first, so the held CC was a plain 7-bit one
+            match self.learn_candidate.take() {
+                Some((candidate, _)) => candidate,
+                None => msg,
+            }
+        };
 
         self.midi_learn.capture(&msg);
+        if let Some(trigger) = self.midi_learn.captured.take() {
+            self.apply_learned_trigger(trigger);
+        }
+    }
+
+    /// Pass on NRPN/RPN values from controllers that only send the data entry MSB
+    fn flush_high_res(&mut self) {
+        for (msg, received) in self.high_res_decoder.flush(std::time::Instant::now()) {
+            self.process_midi_message(msg, received);
+        }
+    }
 
-        if let Ok(matcher) = self.preset_matcher.lock() {
-            if let Some(preset_name) = matcher.handle_midi(&msg) {
-                self.midi_log.add(format!("Executing preset: {}", preset_name));
+    /// Learn a held back CC once no high resolution message has followed it
+    fn flush_learn_candidate(&mut self) {
+        let expired = self
+            .learn_candidate
+            .as_ref()
+            .is_some_and(|(_, held)| held.elapsed() >= LEARN_COMBINE_WINDOW);
+        if expired {
+            if let Some((candidate, _)) = self.learn_candidate.take() {
+                self.midi_learn.capture(&candidate);
+                if let Some(trigger) = self.midi_learn.captured.take() {
+                    self.apply_learned_trigger(trigger);
+                }
             }
         }
     }
 
+    fn handle_tap(&mut self, at: std::time::Instant) {
+        let Some(bpm) = self.tap_tempo.tap(at) else {
+            return;
+        };
+
+        let bpm = (bpm * 10.0).round() / 10.0;
+        self.tap_bpm = Some(bpm);
+        let send = self.tempo.report(TempoSource::TapTempo, bpm);
+        if send && self.connection_state == ConnectionState::Connected {
+            let _ = self.action_tx.send(ActionCommand::SendBpm(bpm));
+        }
+    }
+
+    fn handle_clock_event(&mut self, event: ClockEvent) {
+        match event {
+            ClockEvent::Beat => {
+                let send = self
+                    .midi_clock
+                    .bpm()
+                    .is_some_and(|bpm| self.tempo.report(TempoSource::MidiClock, bpm));
+                if self.connection_state != ConnectionState::Connected {
+                    return;
+                }
+
+                // Send the clock tempo rounded to 0.1 BPM, but only once it has
+                // moved by the resend threshold
+                if let Some(bpm) = self.midi_clock.bpm().filter(|_| send) {
+                    let bpm = (bpm * 10.0).round() / 10.0;
+                    let unchanged = matches!(
+                        self.last_sent_bpm,
+                        Some(last) if (last - bpm).abs() < tempo::BPM_RESEND_THRESHOLD
+                    );
+                    if !unchanged {
+                        self.last_sent_bpm = Some(bpm);
+                        let _ = self.action_tx.send(ActionCommand::SendBpm(bpm));
+                    }
+                }
+                let _ = self.action_tx.send(ActionCommand::SendBeat);
+            }
+            ClockEvent::Started => self.midi_log.add("MIDI Clock started".to_string()),
+            ClockEvent::Stopped => self.midi_log.add("MIDI Clock stopped".to_string()),
+        }
+    }
+
     fn render_midi_panel(&mut self, ui: &Ui) -> Option<usize> {
         let mut port_change_request: Option<usize> = None;
         let mut pending_trigger: Option<MidiTrigger> = None;
@@ -361,6 +928,82 @@ impl AppState {
 
                 ui.separator();
 
+                // MIDI Timecode position and cue list mode
+                match self.current_timecode {
+                    Some((timecode, rate)) => {
+                        ui.text(format!("Timecode: {} @ {}", timecode, rate.name()));
+                    }
+                    None => ui.text_disabled("Timecode: --:--:--:--"),
+                }
+                match self.midi_clock.bpm() {
+                    Some(bpm) => {
+                        let status = if self.midi_clock.is_running() { "running" } else { "stopped" };
+                        ui.text(format!("MIDI Clock: {:.1} BPM ({})", bpm, status));
+                    }
+                    None => ui.text_disabled("MIDI Clock: --"),
+                }
+
+                // Tap tempo binding
+                let tap_binding = self.config.tap_tempo_trigger
+                    .as_ref()
+                    .map(|t| t.display_name())
+                    .unwrap_or_else(|| "not set".to_string());
+                ui.text(format!("Tap Tempo: {}", tap_binding));
+                if self.learn_target == Some(LearnTarget::TapTempo) {
+                    if ui.small_button("Listening... (Cancel)##tap_learn") {
+                        self.cancel_learn();
+                    }
+                } else if ui.small_button("Learn##tap_learn") {
+                    self.start_learn(LearnTarget::TapTempo);
+                }
+                if ui.is_item_hovered() {
+                    ui.tooltip_text("Send a MIDI message to use it as the tap tempo pad");
+                }
+                ui.same_line();
+                ui.disabled(self.config.tap_tempo_trigger.is_none(), || {
+                    if ui.small_button("Clear##tap_learn") {
+                        self.config.tap_tempo_trigger = None;
+                        self.save_config();
+                    }
+                });
+                if let Some(bpm) = self.tap_bpm {
+                    ui.same_line();
+                    ui.text(format!("{:.1} BPM", bpm));
+                }
+
+                let mut cue_list = self.config.timecode_cue_list;
+                if ui.checkbox("Timecode cue list", &mut cue_list) {
+                    self.config.timecode_cue_list = cue_list;
+                    self.save_config();
+                    let _ = self.matcher_tx.send(MatcherCommand::SetCueListEnabled(cue_list));
+                }
+                if ui.is_item_hovered() {
+                    ui.tooltip_text("Fire presets when incoming MIDI Timecode crosses their timecode cue");
+                }
+
+                let mut timings = self.config.gesture_timings;
+                ui.set_next_item_width(150.0);
+                let double_tap_changed = ui.slider("Double tap (ms)", 100, 1000, &mut timings.double_tap_ms);
+                if ui.is_item_hovered() {
+                    ui.tooltip_text("Longest gap between the taps of a double tap gesture");
+                }
+                ui.set_next_item_width(150.0);
+                let long_press_changed = ui.slider("Long press (ms)", 200, 3000, &mut timings.long_press_ms);
+                if ui.is_item_hovered() {
+                    ui.tooltip_text("How long a pad has to be held for a long press gesture");
+                }
+                if double_tap_changed || long_press_changed {
+                    self.config.gesture_timings = timings;
+                    self.save_config();
+                    let _ = self.matcher_tx.send(MatcherCommand::SetGestureTimings(timings));
+                }
+
+                if ui.collapsing_header("Fader Mappings", TreeNodeFlags::empty()) {
+                    self.render_fader_mappings(ui);
+                }
+
+                ui.separator();
+
                 if ui.collapsing_header("MIDI Messages", TreeNodeFlags::DEFAULT_OPEN) {
                     ui.child_window("##midi_tree")
                         .size([0.0, 0.0])
@@ -380,10 +1023,12 @@ impl AppState {
                             for (category, messages) in &self.midi_messages {
                                 if ui.tree_node_config(category).default_open(true).build(|| {
                                     for msg in messages {
-                                        let display = msg.display_name();
+                                        let key = msg.monitor_key();
+                                        // Keep the ID stable while the value in the label changes
+                                        let display = format!("{}###{}", msg.display_name(), key);
                                         
                                         // Check if this message is flashing
-                                        let is_flashing = if let Some(flash_time) = self.flashing_messages.get(&display) {
+                                        let is_flashing = if let Some(flash_time) = self.flashing_messages.get(&key) {
                                             let age = (current_time - flash_time) as f32;
                                             if age < 1.0f32 {
                                                 Some(age)
@@ -450,6 +1095,42 @@ impl AppState {
         port_change_request
     }
 
+    fn render_fader_mappings(&mut self, ui: &Ui) {
+        if self.learn_target == Some(LearnTarget::FaderMapping) {
+            if ui.small_button("Listening... (Cancel)##fader_learn") {
+                self.cancel_learn();
+            }
+        } else if ui.small_button("Learn##fader_learn") {
+            self.start_learn(LearnTarget::FaderMapping);
+        }
+        if ui.is_item_hovered() {
+            ui.tooltip_text("Move a fader or knob on your MIDI surface to map it to a controller fader");
+        }
+
+        let mut changed = false;
+        let mut remove_idx: Option<usize> = None;
+        for (i, mapping) in self.config.fader_mappings.iter_mut().enumerate() {
+            let label = format!("{}###fader_mapping_{}", mapping.display_name(), i);
+            if let Some(_node) = ui.tree_node(&label) {
+                changed |= render_fader_mapping_editor(ui, i, mapping);
+                if ui.small_button(format!("Remove##fader_mapping_{}", i)) {
+                    remove_idx = Some(i);
+                }
+            }
+        }
+        if let Some(i) = remove_idx {
+            self.config.fader_mappings.remove(i);
+            changed = true;
+        }
+        if self.config.fader_mappings.is_empty() {
+            ui.text_disabled("No fader mappings");
+        }
+
+        if changed {
+            self.save_config();
+        }
+    }
+
     fn render_preset_panel(&mut self, ui: &Ui) {
         ui.child_window("##preset_panel")
             .size([500.0, 0.0])
@@ -520,6 +1201,14 @@ impl AppState {
                     });
                 }
 
+                ui.same_line();
+                if ui.button("History") {
+                    self.show_history_modal = true;
+                }
+                if ui.is_item_hovered() {
+                    ui.tooltip_text("Past preset executions with when each command was sent");
+                }
+
                 ui.separator();
 
                 if let Some(idx) = self.selected_preset {
@@ -541,9 +1230,14 @@ impl AppState {
                         self.edit_preset_name = preset_name.clone();
                         self.edit_preset_desc = preset_desc.clone();
                         self.edit_preset_delay = preset_delay;
+                        self.edit_preset_group = self.presets[preset_idx].group.clone().unwrap_or_default();
                         self.pending_edit_preset = Some(idx);
                         self.show_edit_preset_modal = true;
                     }
+                    if let Some(status) = self.preset_status.get(&self.presets[preset_idx].id) {
+                        ui.same_line();
+                        render_status_badge(ui, status);
+                    }
                     ui.text_disabled(&preset_desc);
                     
                     ui.separator();
@@ -564,6 +1258,77 @@ impl AppState {
                     if ui.is_item_hovered() {
                         ui.tooltip_text("Delay before executing preset actions (in seconds). Use 0 for immediate execution.");
                     }
+
+                    let mut momentary = self.presets[preset_idx].momentary;
+                    if ui.checkbox("Momentary", &mut momentary) {
+                        self.presets[preset_idx].momentary = momentary;
+                        let _ = self.save_presets();
+                    }
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text("Run the mirrored actions (Press becomes Release) when the triggering note is released, like a flash button");
+                    }
+
+                    let policies = [
+                        RetriggerPolicy::Restart,
+                        RetriggerPolicy::Ignore,
+                        RetriggerPolicy::Queue,
+                    ];
+                    let current_policy = self.presets[preset_idx].retrigger;
+                    ui.set_next_item_width(100.0);
+                    if let Some(_token) = ui.begin_combo("Retrigger", current_policy.name()) {
+                        for policy in policies {
+                            let selected = current_policy == policy;
+                            if ui.selectable_config(policy.name()).selected(selected).build() && !selected {
+                                self.presets[preset_idx].retrigger = policy;
+                                let _ = self.save_presets();
+                            }
+                        }
+                    }
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text("What happens when the preset fires again while it is still running");
+                    }
+                    if let Some(group) = &self.presets[preset_idx].group {
+                        ui.same_line();
+                        ui.text(format!("Group: {}", group));
+                    }
+
+                    // Timecode cue stamp
+                    match self.presets[preset_idx].timecode {
+                        Some(timecode) => ui.text(format!("Timecode Cue: {}", timecode)),
+                        None => ui.text("Timecode Cue: none"),
+                    }
+                    ui.set_next_item_width(100.0);
+                    ui.input_text("##timecode_cue", &mut self.timecode_cue_input)
+                        .hint("HH:MM:SS:FF")
+                        .build();
+                    ui.same_line();
+                    if ui.small_button("Set##timecode_cue") {
+                        match Timecode::parse(&self.timecode_cue_input) {
+                            Ok(timecode) => {
+                                self.presets[preset_idx].timecode = Some(timecode);
+                                self.timecode_cue_input.clear();
+                                let _ = self.save_presets();
+                            }
+                            Err(e) => self.midi_log.add(format!("Invalid timecode: {}", e)),
+                        }
+                    }
+                    ui.same_line();
+                    ui.disabled(self.current_timecode.is_none(), || {
+                        if ui.small_button("Stamp##timecode_cue") {
+                            if let Some((timecode, _)) = self.current_timecode {
+                                self.presets[preset_idx].timecode = Some(timecode);
+                                let _ = self.save_presets();
+                            }
+                        }
+                    });
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text("Use the current incoming timecode as this preset's cue");
+                    }
+                    ui.same_line();
+                    if ui.small_button("Clear##timecode_cue") {
+                        self.presets[preset_idx].timecode = None;
+                        let _ = self.save_presets();
+                    }
                     
                     ui.separator();
                     
@@ -574,7 +1339,8 @@ impl AppState {
                     ui.disabled(!can_run, || {
                         if ui.button("Run Preset") {
                             let preset_clone = self.presets[preset_idx].clone();
-                            let _ = self.action_tx.send(ActionCommand::ExecutePreset(preset_clone));
+                            let trigger = TriggerSource::manual("Run Preset button");
+                            let _ = self.action_tx.send(ActionCommand::ExecutePreset(preset_clone, trigger));
                             self.midi_log.add(format!("Manually running preset: {}", preset_name));
                         }
                     });
@@ -587,6 +1353,15 @@ impl AppState {
                             ui.tooltip_text("Add actions to this preset to run it");
                         }
                     }
+
+                    ui.same_line();
+                    if ui.button("Stop All") {
+                        let _ = self.action_tx.send(ActionCommand::StopAll);
+                        self.midi_log.add("Stopped all running presets".to_string());
+                    }
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text("Abort every running and queued preset, including delayed action chains and commands not yet sent");
+                    }
                     
                     ui.separator();
 
@@ -600,6 +1375,17 @@ impl AppState {
                             let _ = self.save_presets();
                         }
                     });
+                    ui.same_line();
+                    if self.learn_target == Some(LearnTarget::PresetTrigger(preset_idx)) {
+                        if ui.small_button("Listening... (Cancel)##trigger_learn") {
+                            self.cancel_learn();
+                        }
+                    } else if ui.small_button("Learn##trigger_learn") {
+                        self.start_learn(LearnTarget::PresetTrigger(preset_idx));
+                    }
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text("Send a MIDI message to add it as a trigger");
+                    }
                     let preset_idx = idx; // Copy the index to avoid borrowing issues
                     ui.child_window("##triggers")
                         .size([0.0, 150.0])
@@ -610,6 +1396,14 @@ impl AppState {
                             for i in 0..triggers_len {
                                 let trigger_display = self.presets[preset_idx].triggers[i].display_name();
                                 ui.bullet_text(&trigger_display);
+                                if trigger_has_options(&self.presets[preset_idx].triggers[i]) {
+                                    ui.same_line();
+                                    if ui.small_button(format!("Edit##trig_{}", i)) {
+                                        self.edit_trigger = Some(self.presets[preset_idx].triggers[i].clone());
+                                        self.pending_edit_trigger = Some((preset_idx, i));
+                                        self.show_edit_trigger_modal = true;
+                                    }
+                                }
                                 ui.same_line();
                                 if ui.small_button(&format!("X##trig_{}", i)) {
                                     self.presets[preset_idx].triggers.remove(i);
@@ -622,6 +1416,33 @@ impl AppState {
                             }
                         });
 
+                    // SysEx pattern entry, "??" matches any byte
+                    ui.text("SysEx Pattern:");
+                    ui.same_line();
+                    ui.set_next_item_width(-50.0);
+                    ui.input_text("##sysex_pattern", &mut self.sysex_pattern_input)
+                        .hint("F0 7F ?? 06 02 F7")
+                        .build();
+                    ui.same_line();
+                    if ui.small_button("Add##sysex_pattern") {
+                        match SysExPattern::parse(&self.sysex_pattern_input) {
+                            Ok(pattern) => {
+                                let trigger = MidiTrigger::SysEx { pattern };
+                                if !self.presets[preset_idx].triggers.contains(&trigger) {
+                                    self.presets[preset_idx].triggers.push(trigger);
+                                    let _ = self.save_presets();
+                                }
+                                self.sysex_pattern_input.clear();
+                            }
+                            Err(e) => {
+                                self.midi_log.add(format!("Invalid SysEx pattern: {}", e));
+                            }
+                        }
+                    }
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text("Add a SysEx trigger. Use ?? for bytes that may have any value.");
+                    }
+
                     ui.separator();
                     ui.text("Actions:");
                     ui.same_line();
@@ -660,6 +1481,24 @@ impl AppState {
                             }
                         }
                     }
+
+                    // Controller commands that aren't tied to a button
+                    ui.same_line();
+                    ui.set_next_item_width(130.0);
+                    if let Some(_token) = ui.begin_combo("##add_command", "Add Command...") {
+                        for command in ControllerAction::controller_commands() {
+                            if ui.selectable(command.name()) {
+                                self.presets[preset_idx].actions.push(PresetAction {
+                                    command,
+                                    delay_secs: 0.0,
+                                });
+                                let _ = self.save_presets();
+                            }
+                        }
+                    }
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text("Add a BPM, Beat, Fade, Freeze or Auto BPM command to this preset");
+                    }
                     
                     let preset_idx = idx; // Copy the index to avoid borrowing issues
                     ui.child_window("##actions")
@@ -695,15 +1534,19 @@ impl AppState {
                             let actions_len = self.presets[preset_idx].actions.len();
                             for i in 0..actions_len {
                                 // Collect data we need first, then drop the borrow
-                                let (button_name, current_action_type, truncated_name, button_name_len) = {
+                                let (current_command, truncated_name, button_name_len) = {
                                     let action = &self.presets[preset_idx].actions[i];
-                                    let button_name_len = action.button_name.len();
+                                    let action_name = match &action.command {
+                                        ControllerAction::Button { button_name, .. } => button_name.clone(),
+                                        command => command.display_name(),
+                                    };
+                                    let button_name_len = action_name.len();
                                     let truncated_name = if button_name_len > MAX_NAME_LENGTH {
-                                        format!("{}", &action.button_name[..MAX_NAME_LENGTH])
+                                        format!("{}", &action_name[..MAX_NAME_LENGTH])
                                     } else {
-                                        action.button_name.clone()
+                                        action_name
                                     };
-                                    (action.button_name.clone(), action.action, truncated_name, button_name_len)
+                                    (action.command.clone(), truncated_name, button_name_len)
                                 };
                                 
                                 // Check if this action is selected
@@ -725,33 +1568,58 @@ impl AppState {
                                 ui.text(bullet_char);
                                 ui.same_line();
                                 
-                                // Action type dropdown for editing
-                                let action_types = ["Press", "Release", "Toggle"];
-                                let current_action_idx = match current_action_type {
-                                    ButtonActionType::Press => 0,
-                                    ButtonActionType::Release => 1,
-                                    ButtonActionType::Toggle => 2,
-                                };
-                                ui.set_next_item_width(80.0);
-                                if let Some(_token) = ui.begin_combo(&format!("##action_type_{}", i), action_types[current_action_idx]) {
-                                    for (idx, action_name) in action_types.iter().enumerate() {
-                                        let selected = current_action_idx == idx;
-                                        if ui.selectable_config(action_name).selected(selected).build() {
-                                            let new_action_type = match idx {
-                                                0 => ButtonActionType::Press,
-                                                1 => ButtonActionType::Release,
-                                                2 => ButtonActionType::Toggle,
-                                                _ => ButtonActionType::Toggle,
-                                            };
-                                            // Now we can mutably borrow since we dropped the immutable borrow
-                                            self.presets[preset_idx].actions[i].action = new_action_type;
+                                match current_command {
+                                    ControllerAction::Button { action: current_action_type, .. } => {
+                                        // Action type dropdown for editing
+                                        let action_types = ["Press", "Release", "Toggle"];
+                                        let current_action_idx = match current_action_type {
+                                            ButtonActionType::Press => 0,
+                                            ButtonActionType::Release => 1,
+                                            ButtonActionType::Toggle => 2,
+                                        };
+                                        ui.set_next_item_width(80.0);
+                                        if let Some(_token) = ui.begin_combo(format!("##action_type_{}", i), action_types[current_action_idx]) {
+                                            for (idx, action_name) in action_types.iter().enumerate() {
+                                                let selected = current_action_idx == idx;
+                                                if ui.selectable_config(action_name).selected(selected).build() {
+                                                    let new_action_type = match idx {
+                                                        0 => ButtonActionType::Press,
+                                                        1 => ButtonActionType::Release,
+                                                        2 => ButtonActionType::Toggle,
+                                                        _ => ButtonActionType::Toggle,
+                                                    };
+                                                    // Now we can mutably borrow since we dropped the immutable borrow
+                                                    if let ControllerAction::Button { action, .. } = &mut self.presets[preset_idx].actions[i].command {
+                                                        *action = new_action_type;
+                                                    }
+                                                    let _ = self.save_presets();
+                                                }
+                                            }
+                                        }
+                                        ui.same_line();
+                                    }
+                                    ControllerAction::Fade { .. } => {
+                                        if ui.small_button(format!("Edit##action_fade_{}", i)) {
+                                            self.edit_fade = Some(current_command.clone());
+                                            self.pending_edit_fade = Some((preset_idx, i));
+                                            self.show_edit_fade_modal = true;
+                                        }
+                                        ui.same_line();
+                                    }
+                                    ControllerAction::Bpm { bpm } => {
+                                        let mut bpm_value = bpm;
+                                        ui.set_next_item_width(80.0);
+                                        if ui.input_float(format!("##action_bpm_{}", i), &mut bpm_value).build() {
+                                            if let ControllerAction::Bpm { bpm } = &mut self.presets[preset_idx].actions[i].command {
+                                                *bpm = bpm_value.clamp(1.0, 999.0);
+                                            }
                                             let _ = self.save_presets();
                                         }
+                                        ui.same_line();
                                     }
+                                    _ => {}
                                 }
                                 
-                                ui.same_line();
-                                
                                 // Make text clickable for selection using a button styled as text
                                 // This ensures X button gets click priority since it's rendered after
                                 let _button_style = if is_selected {
@@ -781,11 +1649,7 @@ impl AppState {
                                 // Show tooltip with instructions or full name
                                 if ui.is_item_hovered() {
                                     if button_name_len > MAX_NAME_LENGTH {
-                                        let full_action_text = format!(
-                                            "{:?} {}",
-                                            current_action_type, button_name
-                                        );
-                                        ui.tooltip_text(&full_action_text);
+                                        ui.tooltip_text(current_command.display_name());
                                     } else {
                                         ui.tooltip_text("Click to select, Up/Down arrows to reorder");
                                     }
@@ -874,12 +1738,22 @@ impl AppState {
                         ui.tooltip_text("Delay before executing preset actions (in seconds). Use 0 for immediate execution.");
                     }
 
+                    ui.text("Group:");
+                    ui.input_text("##edit_group", &mut self.edit_preset_group)
+                        .hint("None")
+                        .build();
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text("Starting a preset aborts running presets of the same group");
+                    }
+
                     if ui.button("Save") {
                         if let Some(idx) = self.pending_edit_preset {
                             if idx < self.presets.len() {
                                 self.presets[idx].name = self.edit_preset_name.clone();
                                 self.presets[idx].description = self.edit_preset_desc.clone();
                                 self.presets[idx].delay_secs = self.edit_preset_delay;
+                                let group = self.edit_preset_group.trim();
+                                self.presets[idx].group = (!group.is_empty()).then(|| group.to_string());
                                 let _ = self.save_presets();
                                 
                                 self.edit_preset_name.clear();
@@ -903,6 +1777,101 @@ impl AppState {
                     }
                 });
 
+                if self.show_edit_trigger_modal {
+                    ui.open_popup("Edit Trigger");
+                }
+
+                ui.popup("Edit Trigger", || {
+                    if let Some(trigger) = self.edit_trigger.as_mut() {
+                        ui.text(trigger.display_name());
+                        ui.separator();
+                        render_trigger_options(ui, trigger);
+                        ui.separator();
+                    }
+
+                    if ui.button("Save") {
+                        if let (Some((preset_idx, trigger_idx)), Some(trigger)) =
+                            (self.pending_edit_trigger, self.edit_trigger.take())
+                        {
+                            if let Some(slot) = self.presets
+                                .get_mut(preset_idx)
+                                .and_then(|p| p.triggers.get_mut(trigger_idx))
+                            {
+                                *slot = trigger;
+                                let _ = self.save_presets();
+                            }
+                        }
+                        self.show_edit_trigger_modal = false;
+                        self.pending_edit_trigger = None;
+                        ui.close_current_popup();
+                    }
+
+                    ui.same_line();
+                    if ui.button("Cancel") {
+                        self.edit_trigger = None;
+                        self.show_edit_trigger_modal = false;
+                        self.pending_edit_trigger = None;
+                        ui.close_current_popup();
+                    }
+                });
+
+                if self.show_history_modal {
+                    ui.open_popup("Execution History");
+                }
+
+                ui.popup("Execution History", || {
+                    self.render_history(ui);
+                    ui.separator();
+                    if ui.button("Clear History") {
+                        if let Err(e) = self.history.clear() {
+                            self.midi_log.add(format!("Failed to clear execution history: {}", e));
+                        }
+                    }
+                    ui.same_line();
+                    if ui.button("Close") {
+                        self.show_history_modal = false;
+                        ui.close_current_popup();
+                    }
+                });
+
+                if self.show_edit_fade_modal {
+                    ui.open_popup("Edit Fade");
+                }
+
+                ui.popup("Edit Fade", || {
+                    if let Some(fade) = self.edit_fade.as_mut() {
+                        ui.text(fade.display_name());
+                        ui.separator();
+                        render_fade_options(ui, fade);
+                        ui.separator();
+                    }
+
+                    if ui.button("Save") {
+                        if let (Some((preset_idx, action_idx)), Some(fade)) =
+                            (self.pending_edit_fade, self.edit_fade.take())
+                        {
+                            if let Some(slot) = self.presets
+                                .get_mut(preset_idx)
+                                .and_then(|p| p.actions.get_mut(action_idx))
+                            {
+                                slot.command = fade;
+                                let _ = self.save_presets();
+                            }
+                        }
+                        self.show_edit_fade_modal = false;
+                        self.pending_edit_fade = None;
+                        ui.close_current_popup();
+                    }
+
+                    ui.same_line();
+                    if ui.button("Cancel") {
+                        self.edit_fade = None;
+                        self.show_edit_fade_modal = false;
+                        self.pending_edit_fade = None;
+                        ui.close_current_popup();
+                    }
+                });
+
                 if self.show_delete_confirm_modal {
                     ui.open_popup("Delete Preset");
                 }
@@ -973,7 +1942,7 @@ impl AppState {
             });
     }
 
-    fn render_button_panel(&mut self, ui: &Ui, ui_tx: &mpsc::UnboundedSender<ActionCommand>) {
+    fn render_button_panel(&mut self, ui: &Ui) {
         ui.child_window("##button_panel")
             .size([0.0, 0.0])
             .border(true)
@@ -997,34 +1966,50 @@ impl AppState {
                     self.save_config();
                 }
                 
-                let connecting = self.connection_state == ConnectionState::Connecting;
-                let is_connected = self.connection_state == ConnectionState::Connected;
-
-                // Connect/Disconnect button - disabled while connecting
-                ui.disabled(connecting, || {
-                    if is_connected {
-                        // Show Disconnect button when connected
-                        if ui.button("Disconnect") {
-                            self.connection_state = ConnectionState::Disconnected;
-                            self.buttons.clear();
-                            self.selected_button_indices.clear();
-                            self.last_clicked_button_index = None;
-                            self.midi_log.add("Disconnected from controller".to_string());
-                            let _ = self.action_tx.send(ActionCommand::Disconnect);
-                        }
-                    } else {
-                        // Show Connect button when disconnected
-                        if ui.button("Connect") {
-                            // Only send connect command if not already connecting
-                            self.connection_state = ConnectionState::Connecting;
-                            self.midi_log.add(format!("Connecting to {}", self.connection_address));
-
-                            let addr = self.connection_address.clone();
-                            let pass = self.connection_password.clone();
-                            let _ = self.action_tx.send(ActionCommand::Connect(addr, pass));
-                        }
+                let is_connected = matches!(
+                    self.connection_state,
+                    ConnectionState::Connected | ConnectionState::Connecting | ConnectionState::BackingOff { .. }
+                );
+
+                if is_connected {
+                    // Show Disconnect button when connected, which also stops any connection attempt in progress
+                    if ui.button("Disconnect") {
+                        self.connection_state = ConnectionState::Disconnected;
+                        self.buttons.clear();
+                        self.selected_button_indices.clear();
+                        self.last_clicked_button_index = None;
+                        self.midi_log.add("Disconnected from controller".to_string());
+                        let _ = self.action_tx.send(ActionCommand::Disconnect);
                     }
-                });
+                } else {
+                    // Show Connect button when disconnected
+                    if ui.button("Connect") {
+                        self.connection_state = ConnectionState::Connecting;
+                        self.midi_log.add(format!("Connecting to {}", self.connection_address));
+
+                        let addr = self.connection_address.clone();
+                        let pass = self.connection_password.clone();
+                        let _ = self.action_tx.send(ActionCommand::Connect(addr, pass));
+                    }
+                }
+
+                if self.connection_state == ConnectionState::Connected {
+                    self.render_link_health(ui);
+                }
+                ui.text(format!(
+                    "Tempo: {:.1} BPM ({})",
+                    self.tempo.bpm(),
+                    self.tempo.settings().source.name()
+                ));
+                if ui.is_item_hovered() {
+                    ui.tooltip_text("Sent back whenever ShowXpress asks for the BPM");
+                }
+                if ui.collapsing_header("Tempo", TreeNodeFlags::empty()) {
+                    self.render_tempo_settings(ui);
+                }
+                if ui.collapsing_header("Heartbeat", TreeNodeFlags::empty()) {
+                    self.render_heartbeat_settings(ui);
+                }
 
                 ui.separator();
 
@@ -1044,18 +2029,11 @@ impl AppState {
                                             let button_name = self.buttons[button_idx].name.clone();
                                             
                                             // Check for duplicate action (same button name and action type)
+                                            let action = PresetAction::button(button_name, action_type);
                                             let is_duplicate = self.presets[preset_idx].actions.iter()
-                                                .any(|existing_action| {
-                                                    existing_action.button_name == button_name
-                                                        && existing_action.action == action_type
-                                                });
+                                                .any(|existing_action| existing_action.command == action.command);
                                             
                                             if !is_duplicate {
-                                                let action = ButtonAction {
-                                                    button_name,
-                                                    action: action_type,
-                                                    delay_secs: 0.0,
-                                                };
                                                 self.presets[preset_idx].actions.push(action);
                                             }
                                         }
@@ -1138,18 +2116,11 @@ impl AppState {
                         for (preset_idx_opt, button_name, action_type) in double_clicked_data.into_inner() {
                             if let Some(preset_idx) = preset_idx_opt {
                                 // Check for duplicate action (same button name and action type)
+                                let action = PresetAction::button(button_name, action_type);
                                 let is_duplicate = self.presets[preset_idx].actions.iter()
-                                    .any(|existing_action| {
-                                        existing_action.button_name == button_name
-                                            && existing_action.action == action_type
-                                    });
+                                    .any(|existing_action| existing_action.command == action.command);
                                 
                                 if !is_duplicate {
-                                    let action = ButtonAction {
-                                        button_name,
-                                        action: action_type,
-                                        delay_secs: 0.0,
-                                    };
                                     self.presets[preset_idx].actions.push(action);
                                     let _ = self.save_presets();
                                 }
@@ -1159,6 +2130,12 @@ impl AppState {
                     ConnectionState::Connecting => {
                         ui.text_disabled("Connecting...");
                     }
+                    ConnectionState::BackingOff { attempt, retry_at, error } => {
+                        let remaining = (*retry_at - Local::now()).num_milliseconds().max(0) as f32 / 1000.0;
+                        let _style = ui.push_style_color(StyleColor::Text, [1.0, 0.8, 0.2, 1.0]);
+                        ui.text_wrapped(format!("Connection lost: {}", error));
+                        ui.text(format!("Retrying in {:.0}s (attempt {})", remaining.ceil(), attempt));
+                    }
                     ConnectionState::Disconnected => {
                         ui.text_disabled("Not connected");
                     }
@@ -1171,6 +2148,383 @@ impl AppState {
     }
 }
 
+/// Whether a trigger has settings that can be changed in the trigger editor
+fn trigger_has_options(trigger: &MidiTrigger) -> bool {
+    !matches!(trigger, MidiTrigger::SysEx { .. } | MidiTrigger::Mmc { .. })
+}
+
+fn render_trigger_options(ui: &Ui, trigger: &mut MidiTrigger) {
+    if let Some(channel) = trigger.channel_mut() {
+        render_channel_editor(ui, channel);
+    }
+
+    let mut widened: Option<MidiTrigger> = None;
+    match trigger {
+        MidiTrigger::NoteOn { channel, note, velocity, gesture } => {
+            render_range_editor(ui, "Velocity", velocity, 127);
+            render_gesture_editor(ui, gesture);
+            if ui.button("Widen to Range") {
+                widened = Some(MidiTrigger::NoteRange {
+                    channel: *channel,
+                    low: *note,
+                    high: note.saturating_add(11).min(127),
+                    velocity: *velocity,
+                    action_per_note: false,
+                });
+            }
+            if ui.is_item_hovered() {
+                ui.tooltip_text("Match a range of notes starting at this one, e.g. a whole octave of pads");
+            }
+            ui.same_line();
+            if ui.button("Convert to Chord") {
+                widened = Some(MidiTrigger::Chord {
+                    channel: *channel,
+                    notes: vec![*note],
+                    window_ms: 100,
+                });
+            }
+            if ui.is_item_hovered() {
+                ui.tooltip_text("Fire only when several notes are held down together");
+            }
+            ui.same_line();
+            if ui.button("Convert to Sequence") {
+                widened = Some(MidiTrigger::Sequence {
+                    channel: *channel,
+                    notes: vec![*note],
+                    timeout_ms: 2000,
+                });
+            }
+            if ui.is_item_hovered() {
+                ui.tooltip_text("Fire when several notes are played one after another");
+            }
+        }
+        MidiTrigger::ControlChange { value, threshold, .. } => {
+            render_threshold_editor(ui, threshold);
+            if threshold.is_none() {
+                render_range_editor(ui, "Value", value, 127);
+            }
+        }
+        MidiTrigger::ChannelPressure { value, .. } | MidiTrigger::PolyAftertouch { value, .. } => {
+            render_range_editor(ui, "Pressure", value, 127)
+        }
+        MidiTrigger::PitchBend { value, .. }
+        | MidiTrigger::Cc14 { value, .. }
+        | MidiTrigger::Nrpn { value, .. }
+        | MidiTrigger::Rpn { value, .. } => render_range_editor(ui, "Value", value, 16383),
+        MidiTrigger::NoteRange { low, high, velocity, action_per_note, .. } => {
+            ui.set_next_item_width(200.0);
+            ui.slider("Lowest Note", 0, 127, low);
+            ui.set_next_item_width(200.0);
+            ui.slider("Highest Note", 0, 127, high);
+            if *low > *high {
+                std::mem::swap(low, high);
+            }
+            render_range_editor(ui, "Velocity", velocity, 127);
+            ui.checkbox("Run one action per note", action_per_note);
+            if ui.is_item_hovered() {
+                ui.tooltip_text("The lowest note runs the first action, the next note the second, and so on");
+            }
+        }
+        MidiTrigger::Chord { notes, window_ms, .. } => {
+            render_note_list(ui, "chord", notes, 4);
+            ui.set_next_item_width(200.0);
+            ui.slider("Window (ms)", 10, 2000, window_ms);
+            if ui.is_item_hovered() {
+                ui.tooltip_text("All chord notes have to be pressed within this time of each other");
+            }
+        }
+        MidiTrigger::Sequence { notes, timeout_ms, .. } => {
+            render_note_list(ui, "seq", notes, 2);
+            ui.set_next_item_width(200.0);
+            ui.slider("Timeout (ms)", 100, 10000, timeout_ms);
+            if ui.is_item_hovered() {
+                ui.tooltip_text("The whole sequence has to be played within this time of its first note");
+            }
+        }
+        _ => ui.text_disabled("This trigger has no options"),
+    }
+
+    if let Some(widened) = widened {
+        *trigger = widened;
+    }
+}
+
+fn render_history_entry(ui: &Ui, id: usize, entry: &HistoryEntry) {
+    let color = match entry.outcome {
+        PresetOutcome::Completed => [0.4, 1.0, 0.4, 1.0],
+        PresetOutcome::Failed(_) => [1.0, 0.3, 0.3, 1.0],
+        PresetOutcome::Cancelled => [0.6, 0.6, 0.6, 1.0],
+    };
+    let label = format!(
+        "{} {} - {} ({} ms)###history_{}",
+        entry.trigger.received.format("%Y-%m-%d %H:%M:%S%.3f"),
+        entry.preset_name,
+        entry.outcome.name(),
+        entry.duration_ms(),
+        id,
+    );
+    let _color = ui.push_style_color(StyleColor::Text, color);
+    if let Some(_node) = ui.tree_node(&label) {
+        drop(_color);
+        ui.text(format!("Trigger: {}", entry.trigger.cause));
+        if let Some(message) = &entry.trigger.message {
+            ui.text(format!("MIDI: {}", message));
+        }
+        ui.text(format!(
+            "Started: {} (+{} ms)",
+            entry.started.format("%H:%M:%S%.3f"),
+            entry.ms_after_trigger(entry.started)
+        ));
+        for action in &entry.actions {
+            match (action.written, &action.error) {
+                (Some(written), _) => ui.text(format!(
+                    "  {} written at {} (+{} ms)",
+                    action.action,
+                    written.format("%H:%M:%S%.3f"),
+                    entry.ms_after_trigger(written)
+                )),
+                (None, error) => ui.text_colored(
+                    [1.0, 0.3, 0.3, 1.0],
+                    format!("  {} failed: {}", action.action, error.as_deref().unwrap_or("unknown error")),
+                ),
+            }
+        }
+        ui.text(format!(
+            "Finished: {} (+{} ms)",
+            entry.finished.format("%H:%M:%S%.3f"),
+            entry.ms_after_trigger(entry.finished)
+        ));
+        if let PresetOutcome::Failed(error) = &entry.outcome {
+            ui.text_colored([1.0, 0.3, 0.3, 1.0], format!("Error: {}", error));
+        }
+    }
+}
+
+fn render_status_badge(ui: &Ui, status: &PresetStatus) {
+    let (color, label) = match &status.outcome {
+        None => ([1.0, 0.8, 0.2, 1.0], "[Running]"),
+        Some(PresetOutcome::Completed) => ([0.4, 1.0, 0.4, 1.0], "[Completed]"),
+        Some(PresetOutcome::Failed(_)) => ([1.0, 0.3, 0.3, 1.0], "[Failed]"),
+        Some(PresetOutcome::Cancelled) => ([0.6, 0.6, 0.6, 1.0], "[Cancelled]"),
+    };
+    ui.text_colored(color, label);
+    if ui.is_item_hovered() {
+        let since = status.at.format("%H:%M:%S%.3f");
+        match &status.outcome {
+            None => ui.tooltip_text(format!("Started at {}", since)),
+            Some(PresetOutcome::Failed(error)) => ui.tooltip_text(format!("{} at {}", error, since)),
+            Some(_) => ui.tooltip_text(format!("Finished at {}", since)),
+        }
+    }
+}
+
+fn render_gesture_editor(ui: &Ui, gesture: &mut Option<NoteGesture>) {
+    let gestures = [
+        None,
+        Some(NoteGesture::Tap),
+        Some(NoteGesture::DoubleTap),
+        Some(NoteGesture::LongPress),
+    ];
+    let label = |g: &Option<NoteGesture>| g.map(|g| g.name()).unwrap_or("Note down");
+
+    ui.set_next_item_width(200.0);
+    if let Some(_token) = ui.begin_combo("Gesture", label(gesture)) {
+        for option in gestures {
+            let selected = *gesture == option;
+            if ui.selectable_config(label(&option)).selected(selected).build() {
+                *gesture = option;
+            }
+        }
+    }
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Fire on a tap, double tap or long press instead of as soon as the note goes down");
+    }
+}
+
+fn render_fade_options(ui: &Ui, fade: &mut ControllerAction) {
+    let ControllerAction::Fade { fader, from, to, duration_secs, curve, rate_hz } = fade else {
+        return;
+    };
+
+    let mut fader_index = *fader as i32;
+    ui.set_next_item_width(150.0);
+    if ui.input_int("Fader", &mut fader_index).build() {
+        *fader = fader_index.max(0) as u32;
+    }
+
+    let mut from_current = from.is_none();
+    if ui.checkbox("Start from current value", &mut from_current) {
+        *from = if from_current { None } else { Some(0) };
+    }
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Start from the last value sent to this fader");
+    }
+    if let Some(from_value) = from {
+        ui.set_next_item_width(150.0);
+        ui.input_int("From", from_value).build();
+    }
+    ui.set_next_item_width(150.0);
+    ui.input_int("To", to).build();
+
+    ui.set_next_item_width(150.0);
+    if ui.input_float("Duration (s)", duration_secs).build() && *duration_secs < 0.0 {
+        *duration_secs = 0.0;
+    }
+
+    ui.set_next_item_width(150.0);
+    if let Some(_token) = ui.begin_combo("Curve", curve.name()) {
+        for option in ResponseCurve::ALL {
+            let selected = *curve == option;
+            if ui.selectable_config(option.name()).selected(selected).build() {
+                *curve = option;
+            }
+        }
+    }
+
+    ui.set_next_item_width(150.0);
+    ui.slider("Updates per second", 1, 100, rate_hz);
+}
+
+/// Settings of one fader mapping, returning whether anything changed
+fn render_fader_mapping_editor(ui: &Ui, i: usize, mapping: &mut FaderMapping) -> bool {
+    let _id = ui.push_id_usize(i);
+    let mut changed = false;
+
+    let mut any_channel = mapping.channel.is_none();
+    if ui.checkbox("Any channel", &mut any_channel) {
+        mapping.channel = if any_channel { None } else { Some(0) };
+        changed = true;
+    }
+    if let Some(channel) = &mut mapping.channel {
+        ui.set_next_item_width(150.0);
+        changed |= ui.slider("Channel", 0, 15, channel);
+    }
+
+    ui.set_next_item_width(150.0);
+    changed |= ui.slider("CC", 0, 127, &mut mapping.cc);
+    changed |= ui.checkbox("14-bit (CC + 32 as LSB)", &mut mapping.high_res);
+
+    let mut fader = mapping.fader as i32;
+    ui.set_next_item_width(150.0);
+    if ui.input_int("Fader", &mut fader).build() {
+        mapping.fader = fader.max(0) as u32;
+        changed = true;
+    }
+    ui.set_next_item_width(150.0);
+    changed |= ui.input_int("Min Value", &mut mapping.out_min).build();
+    ui.set_next_item_width(150.0);
+    changed |= ui.input_int("Max Value", &mut mapping.out_max).build();
+    changed |= ui.checkbox("Invert", &mut mapping.invert);
+
+    ui.set_next_item_width(150.0);
+    if let Some(_token) = ui.begin_combo("Curve", mapping.curve.name()) {
+        for curve in ResponseCurve::ALL {
+            let selected = mapping.curve == curve;
+            if ui.selectable_config(curve.name()).selected(selected).build() {
+                mapping.curve = curve;
+                changed = true;
+            }
+        }
+    }
+
+    changed
+}
+
+/// Editable list of notes for chord and sequence triggers; "Add Note" adds the
+/// last note raised by `step` semitones
+fn render_note_list(ui: &Ui, id: &str, notes: &mut Vec<u8>, step: u8) {
+    let mut remove_idx: Option<usize> = None;
+    for (i, note) in notes.iter_mut().enumerate() {
+        ui.set_next_item_width(200.0);
+        ui.slider(format!("Note {}##{}_{}", i + 1, id, i), 0, 127, note);
+        ui.same_line();
+        ui.text(format!("{}{}", note_name(*note), note_octave(*note)));
+        ui.same_line();
+        if ui.small_button(format!("Remove##{}_{}", id, i)) {
+            remove_idx = Some(i);
+        }
+    }
+    if let Some(i) = remove_idx {
+        notes.remove(i);
+    }
+    if ui.button("Add Note") {
+        let next = notes
+            .last()
+            .map(|n| n.saturating_add(step).min(127))
+            .unwrap_or(60);
+        notes.push(next);
+    }
+}
+
+fn render_channel_editor(ui: &Ui, channel: &mut Option<u8>) {
+    let mut any_channel = channel.is_none();
+    if ui.checkbox("Any channel", &mut any_channel) {
+        *channel = if any_channel { None } else { Some(0) };
+    }
+    if let Some(current) = channel {
+        ui.set_next_item_width(200.0);
+        ui.slider("Channel", 0, 15, current);
+    }
+}
+
+/// Edge trigger settings for CC triggers
+fn render_threshold_editor(ui: &Ui, threshold: &mut Option<CcThreshold>) {
+    let mut edge = threshold.is_some();
+    if ui.checkbox("Fire on threshold crossing", &mut edge) {
+        *threshold = edge.then_some(CcThreshold {
+            level: 64,
+            direction: CrossingDirection::Rising,
+            hysteresis: 2,
+        });
+    }
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Fire once when the value crosses the threshold instead of on every message");
+    }
+
+    if let Some(current) = threshold {
+        ui.set_next_item_width(200.0);
+        ui.slider("Threshold", 0, 127, &mut current.level);
+
+        let directions = [
+            CrossingDirection::Rising,
+            CrossingDirection::Falling,
+            CrossingDirection::Either,
+        ];
+        ui.set_next_item_width(200.0);
+        if let Some(_token) = ui.begin_combo("Direction", current.direction.name()) {
+            for direction in directions {
+                let selected = current.direction == direction;
+                if ui.selectable_config(direction.name()).selected(selected).build() {
+                    current.direction = direction;
+                }
+            }
+        }
+
+        ui.set_next_item_width(200.0);
+        ui.slider("Hysteresis", 0, 32, &mut current.hysteresis);
+    }
+}
+
+/// Optional min/max editor; an unset range matches any value
+fn render_range_editor(ui: &Ui, label: &str, range: &mut Option<ValueRange>, max: u16) {
+    let mut limited = range.is_some();
+    if ui.checkbox(format!("Limit {}", label), &mut limited) {
+        *range = limited.then(|| ValueRange::new(0, max));
+    }
+
+    if let Some(current) = range {
+        let mut min_value = current.min;
+        let mut max_value = current.max;
+        ui.set_next_item_width(200.0);
+        let min_changed = ui.slider(format!("Min {}", label), 0, max, &mut min_value);
+        ui.set_next_item_width(200.0);
+        let max_changed = ui.slider(format!("Max {}", label), 0, max, &mut max_value);
+        if min_changed || max_changed {
+            *current = ValueRange::new(min_value, max_value);
+        }
+    }
+}
+
 fn connect_midi_port(
     port_idx: usize,
     available_ports: &[String],
@@ -1200,13 +2554,20 @@ fn connect_midi_port(
         .unwrap_or_else(|_| format!("Port {}", port_idx));
     
     let state_midi = Arc::clone(&state);
+    let mut origin: Option<(u64, std::time::Instant)> = None;
     let conn = midi_in.connect(
         &ports[port_idx],
         "midi-listener",
-        move |_timestamp, message, _| {
+        move |timestamp, message, _| {
+            // midir stamps messages in microseconds from an arbitrary start. Anchored
+            // to the first message, the stamps keep clock and tap timing accurate
+            // however long the UI holds the state lock.
+            let (origin_us, origin_at) =
+                *origin.get_or_insert_with(|| (timestamp, std::time::Instant::now()));
+            let received = origin_at + std::time::Duration::from_micros(timestamp.saturating_sub(origin_us));
             if let Some(midi_msg) = MidiMessage::from_raw(message) {
                 if let Ok(mut state) = state_midi.lock() {
-                    state.handle_midi_message(midi_msg);
+                    state.handle_midi_message(midi_msg, received);
                 }
             }
         },
@@ -1254,22 +2615,36 @@ fn run() -> Result<()> {
     // UI sends commands to executor:
     let (action_tx, action_rx) = mpsc::unbounded_channel::<ActionCommand>();
 
-    // Executor sends results back to UI:
-    let (ui_tx, mut ui_rx) = mpsc::unbounded_channel::<ActionCommand>();
+    // Executor and matcher report what happened back to UI:
+    let (event_tx, mut event_rx) = mpsc::unbounded_channel::<ExecutorEvent>();
 
-    let ui_tx_for_executor = ui_tx.clone();
+    // Tempo is shared so the controller connection can answer BPM requests
+    let tempo = SharedTempo::default();
+
+    let event_tx_for_executor = event_tx.clone();
+    let tempo_for_executor = tempo.clone();
     tokio::spawn(async move {
-        let mut executor = ActionExecutor::new(action_rx, ui_tx_for_executor);
+        let mut executor = ActionExecutor::new(action_rx, event_tx_for_executor, tempo_for_executor);
         executor.run().await;
     });
 
+    // MIDI messages are matched against presets on their own task, so gesture timers can run
+    let (matcher_tx, matcher_rx) = mpsc::unbounded_channel::<MatcherCommand>();
+    let action_tx_for_matcher = action_tx.clone();
+    tokio::spawn(async move {
+        let mut matcher = PresetMatcher::new(matcher_rx, action_tx_for_matcher, event_tx);
+        matcher.run().await;
+    });
+
     let storage = PresetStorage::new()?;
     let midi_connection = Arc::new(Mutex::new(None));
     let state = Arc::new(Mutex::new(AppState::new(
         storage,
         action_tx.clone(),
+        matcher_tx,
         available_midi_ports.clone(),
         Arc::clone(&midi_connection),
+        tempo,
     )?));
 
     // Connect to initial port if available
@@ -1504,34 +2879,12 @@ fn run() -> Result<()> {
                     .resizable(false)
                     .build(|| {
                         if let Ok(mut state) = state.lock() {
-                            // Process any connection results
-                            while let Ok(cmd) = ui_rx.try_recv() {
-                                match cmd {
-                                    ActionCommand::ConnectionSuccess(buttons) => {
-                                        let button_count = buttons.len();
-                                        // Only mark as updated if the list actually changed
-                                        let buttons_changed = state.buttons != buttons;
-                                        if buttons_changed {
-                                            state.buttons_just_updated = true;
-                                        }
-                                        state.buttons = buttons;
-                                        // If button list is empty, we're disconnected (sent by Disconnect command)
-                                        if button_count == 0 {
-                                            state.connection_state = ConnectionState::Disconnected;
-                                        } else {
-                                            state.connection_state = ConnectionState::Connected;
-                                            if buttons_changed {
-                                                state.midi_log.add(format!("Connected! Loaded {} buttons", button_count));
-                                            }
-                                        }
-                                    }
-                                    ActionCommand::ConnectionError(err) => {
-                                        state.connection_state = ConnectionState::Error(err.clone());
-                                        state.midi_log.add(format!("Connection error: {}", err));
-                                    }
-                                    _ => {}
-                                }
+                            // Process connection results and execution events
+                            while let Ok(event) = event_rx.try_recv() {
+                                state.handle_executor_event(event);
                             }
+                            state.flush_high_res();
+                            state.flush_learn_candidate();
 
                             if let Some(new_port_idx) = state.render_midi_panel(&ui) {
                                 port_change_request = Some(new_port_idx);
@@ -1539,7 +2892,7 @@ fn run() -> Result<()> {
                             ui.same_line();
                             state.render_preset_panel(&ui);
                             ui.same_line();
-                            state.render_button_panel(&ui, &ui_tx);
+                            state.render_button_panel(&ui);
                         }
                     });
 
@@ -0,0 +1,202 @@
+use std::time::{Duration, Instant};
+
+use crate::models::MidiMessage;
+
+const CC_NRPN_LSB: u8 = 98;
+const CC_NRPN_MSB: u8 = 99;
+const CC_RPN_LSB: u8 = 100;
+const CC_RPN_MSB: u8 = 101;
+const CC_DATA_ENTRY_MSB: u8 = 6;
+const CC_DATA_ENTRY_LSB: u8 = 38;
+
+/// How long a data entry MSB waits for its LSB before it is sent on its own
+const DATA_ENTRY_LSB_WAIT: Duration = Duration::from_millis(20);
+
+#[derive(Clone, Copy, PartialEq)]
+enum ParamKind {
+    Nrpn,
+    Rpn,
+}
+
+#[derive(Clone, Copy, Default)]
+struct ChannelState {
+    /// Last MSB seen for each of CC 0-31
+    msb: [Option<u8>; 32],
+    param_kind: Option<ParamKind>,
+    param_msb: Option<u8>,
+    param_lsb: Option<u8>,
+    data_msb: Option<u8>,
+    /// When a data entry MSB arrived that no LSB has followed yet
+    msb_waiting_since: Option<Instant>,
+}
+
+impl ChannelState {
+    fn param(&self) -> Option<(ParamKind, u16)> {
+        let kind = self.param_kind?;
+        let param = ((self.param_msb? as u16) << 7) | self.param_lsb? as u16;
+        Some((kind, param))
+    }
+
+    fn select_param(&mut self, kind: ParamKind) {
+        if self.param_kind != Some(kind) {
+            self.param_kind = Some(kind);
+            self.param_msb = None;
+            self.param_lsb = None;
+        }
+        self.data_msb = None;
+        self.msb_waiting_since = None;
+    }
+}
+
+/// Assembles high resolution controller data that arrives spread over several
+/// CC messages: 14-bit MSB/LSB pairs (CC 0-31 with CC 32-63) and NRPN/RPN
+/// parameter changes (CC 99/98 or 101/100 followed by data entry CC 6/38).
+/// Like the CC pairs, a parameter value is emitted when its LSB arrives, or
+/// from the MSB alone via `flush` for controllers that never send the LSB.
+///
+/// The raw CC messages still go through as usual; the decoder only produces
+/// the combined message once enough of it has arrived.
+pub struct HighResDecoder {
+    channels: [ChannelState; 16],
+}
+
+impl HighResDecoder {
+    pub fn new() -> Self {
+        Self {
+            channels: [ChannelState::default(); 16],
+        }
+    }
+
+    /// Take in a message that arrived at `now`
+    pub fn feed(&mut self, msg: &MidiMessage, now: Instant) -> Option<MidiMessage> {
+        let MidiMessage::ControlChange { channel, cc, value } = *msg else {
+            return None;
+        };
+        let state = &mut self.channels[(channel & 0x0F) as usize];
+
+        match cc {
+            CC_NRPN_MSB | CC_NRPN_LSB | CC_RPN_MSB | CC_RPN_LSB => {
+                let kind = if cc == CC_NRPN_MSB || cc == CC_NRPN_LSB {
+                    ParamKind::Nrpn
+                } else {
+                    ParamKind::Rpn
+                };
+                state.select_param(kind);
+                if cc == CC_NRPN_MSB || cc == CC_RPN_MSB {
+                    state.param_msb = Some(value);
+                } else {
+                    state.param_lsb = Some(value);
+                }
+
+                // RPN 127/127 is the "null" parameter that deselects data entry
+                if kind == ParamKind::Rpn && state.param_msb == Some(127) && state.param_lsb == Some(127) {
+                    state.param_kind = None;
+                }
+                None
+            }
+            // Hold the MSB so a full 14-bit change fires once, when its LSB arrives
+            CC_DATA_ENTRY_MSB if state.param().is_some() => {
+                state.data_msb = Some(value);
+                state.msb_waiting_since = Some(now);
+                None
+            }
+            CC_DATA_ENTRY_LSB if state.param().is_some() => {
+                let msb = state.data_msb?;
+                state.msb_waiting_since = None;
+                param_message(channel, state.param()?, ((msb as u16) << 7) | value as u16)
+            }
+            0..=31 => {
+                state.msb[cc as usize] = Some(value);
+                None
+            }
+            32..=63 => {
+                let msb = state.msb[(cc - 32) as usize]?;
+                Some(MidiMessage::Cc14 {
+                    channel,
+                    cc: cc - 32,
+                    value14: ((msb as u16) << 7) | value as u16,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Parameter values whose data entry MSB has waited out the LSB window,
+    /// with the time each MSB arrived
+    pub fn flush(&mut self, now: Instant) -> Vec<(MidiMessage, Instant)> {
+        let mut flushed = Vec::new();
+        for (channel, state) in self.channels.iter_mut().enumerate() {
+            let Some(since) = state.msb_waiting_since else {
+                continue;
+            };
+            if now.saturating_duration_since(since) < DATA_ENTRY_LSB_WAIT {
+                continue;
+            }
+            state.msb_waiting_since = None;
+            if let (Some(param), Some(msb)) = (state.param(), state.data_msb) {
+                if let Some(msg) = param_message(channel as u8, param, (msb as u16) << 7) {
+                    flushed.push((msg, since));
+                }
+            }
+        }
+        flushed
+    }
+}
+
+/// Whether a message may be part of a 14-bit pair or an NRPN/RPN change,
+/// so MIDI learn should wait for the combined message before taking it
+pub fn may_combine(msg: &MidiMessage) -> bool {
+    matches!(
+        msg,
+        MidiMessage::ControlChange { cc: 0..=63 | CC_NRPN_LSB..=CC_RPN_MSB, .. }
+    )
+}
+
+fn param_message(channel: u8, (kind, param): (ParamKind, u16), value14: u16) -> Option<MidiMessage> {
+    Some(match kind {
+        ParamKind::Nrpn => MidiMessage::Nrpn { channel, param, value14 },
+        ParamKind::Rpn => MidiMessage::Rpn { channel, param, value14 },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cc(cc: u8, value: u8) -> MidiMessage {
+        MidiMessage::ControlChange { channel: 0, cc, value }
+    }
+
+    #[test]
+    fn data_entry_msb_then_lsb_emits_once() {
+        let mut decoder = HighResDecoder::new();
+        let now = Instant::now();
+        assert!(decoder.feed(&cc(CC_NRPN_MSB, 1), now).is_none());
+        assert!(decoder.feed(&cc(CC_NRPN_LSB, 2), now).is_none());
+        assert!(decoder.feed(&cc(CC_DATA_ENTRY_MSB, 64), now).is_none());
+
+        let msg = decoder.feed(&cc(CC_DATA_ENTRY_LSB, 5), now);
+        assert!(matches!(
+            msg,
+            Some(MidiMessage::Nrpn { channel: 0, param: 130, value14: 8197 })
+        ));
+        assert!(decoder.flush(now + DATA_ENTRY_LSB_WAIT).is_empty());
+    }
+
+    #[test]
+    fn data_entry_msb_alone_emits_after_the_lsb_window() {
+        let mut decoder = HighResDecoder::new();
+        let now = Instant::now();
+        assert!(decoder.feed(&cc(CC_RPN_MSB, 0), now).is_none());
+        assert!(decoder.feed(&cc(CC_RPN_LSB, 0), now).is_none());
+        assert!(decoder.feed(&cc(CC_DATA_ENTRY_MSB, 2), now).is_none());
+        assert!(decoder.flush(now).is_empty());
+
+        let flushed = decoder.flush(now + DATA_ENTRY_LSB_WAIT);
+        assert!(matches!(
+            flushed.as_slice(),
+            [(MidiMessage::Rpn { channel: 0, param: 0, value14: 256 }, at)] if *at == now
+        ));
+        assert!(decoder.flush(now + DATA_ENTRY_LSB_WAIT * 2).is_empty());
+    }
+}
@@ -1,15 +1,50 @@
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::timecode::Timecode;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Preset {
     pub id: Uuid,
     pub name: String,
     pub description: String,
     pub triggers: Vec<MidiTrigger>,
-    pub actions: Vec<ButtonAction>,
+    pub actions: Vec<PresetAction>,
     #[serde(default = "default_preset_delay")]
     pub delay_secs: f32,
+    /// Cue list stamp; the preset fires when incoming MTC crosses it
+    #[serde(default)]
+    pub timecode: Option<Timecode>,
+    /// Run the mirrored release actions when the triggering note goes back up
+    #[serde(default)]
+    pub momentary: bool,
+    /// What happens when the preset fires again while it is still running
+    #[serde(default)]
+    pub retrigger: RetriggerPolicy,
+    /// Starting a preset aborts running presets of the same group
+    #[serde(default)]
+    pub group: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RetriggerPolicy {
+    /// Abort the running execution and start over
+    #[default]
+    Restart,
+    /// Keep the running execution and drop the new one
+    Ignore,
+    /// Run the new execution once the running one has finished
+    Queue,
+}
+
+impl RetriggerPolicy {
+    pub fn name(&self) -> &'static str {
+        match self {
+            RetriggerPolicy::Restart => "Restart",
+            RetriggerPolicy::Ignore => "Ignore",
+            RetriggerPolicy::Queue => "Queue",
+        }
+    }
 }
 
 fn default_preset_delay() -> f32 {
@@ -25,73 +60,686 @@ impl Preset {
             triggers: Vec::new(),
             actions: Vec::new(),
             delay_secs: 0.1,
+            timecode: None,
+            momentary: false,
+            retrigger: RetriggerPolicy::default(),
+            group: None,
         }
     }
+
+    /// Copy of this preset with every action swapped for its opposite, used
+    /// to undo a momentary preset when its note is released. Actions without
+    /// an opposite, like BPM and Beat, are left out.
+    pub fn mirrored(&self) -> Self {
+        let mut preset = self.clone();
+        preset.actions = self
+            .actions
+            .iter()
+            .filter_map(|action| {
+                Some(PresetAction {
+                    command: action.command.mirrored()?,
+                    delay_secs: action.delay_secs,
+                })
+            })
+            .collect();
+        // A release always runs, cutting short any press actions still pending
+        preset.retrigger = RetriggerPolicy::Restart;
+        // Undoing this preset must not take over from the rest of its group
+        preset.group = None;
+        preset
+    }
 }
 
+/// A MIDI condition that fires a preset. A `channel` of `None` matches any channel.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum MidiTrigger {
-    NoteOn { channel: u8, note: u8 },
-    NoteOff { channel: u8, note: u8 },
-    ControlChange { channel: u8, cc: u8, value: Option<u8> },
+    NoteOn {
+        channel: Option<u8>,
+        note: u8,
+        #[serde(default)]
+        velocity: Option<ValueRange>,
+        /// When set the trigger fires on the gesture instead of the note going down
+        #[serde(default)]
+        gesture: Option<NoteGesture>,
+    },
+    NoteOff { channel: Option<u8>, note: u8 },
+    ControlChange {
+        channel: Option<u8>,
+        cc: u8,
+        value: Option<ValueRange>,
+        /// When set the trigger only fires on threshold crossings, see `PresetMatcher`
+        #[serde(default)]
+        threshold: Option<CcThreshold>,
+    },
+    ProgramChange { channel: Option<u8>, program: u8 },
+    PitchBend {
+        channel: Option<u8>,
+        /// Bend position, 0-16383 with 8192 at rest
+        #[serde(default)]
+        value: Option<ValueRange>,
+    },
+    ChannelPressure {
+        channel: Option<u8>,
+        #[serde(default)]
+        value: Option<ValueRange>,
+    },
+    PolyAftertouch {
+        channel: Option<u8>,
+        note: u8,
+        #[serde(default)]
+        value: Option<ValueRange>,
+    },
+    SysEx { pattern: SysExPattern },
+    Mmc { command: MmcCommand },
+    Cc14 { channel: Option<u8>, cc: u8, value: Option<ValueRange> },
+    Nrpn { channel: Option<u8>, param: u16, value: Option<ValueRange> },
+    Rpn { channel: Option<u8>, param: u16, value: Option<ValueRange> },
+    /// Note On anywhere in `low..=high`
+    NoteRange {
+        channel: Option<u8>,
+        low: u8,
+        high: u8,
+        #[serde(default)]
+        velocity: Option<ValueRange>,
+        /// Run only the action at the note's position in the range
+        #[serde(default)]
+        action_per_note: bool,
+    },
+    /// All `notes` held down together, pressed within `window_ms` of each other
+    Chord {
+        channel: Option<u8>,
+        notes: Vec<u8>,
+        window_ms: u32,
+    },
+    /// `notes` played in order within `timeout_ms`; a wrong note starts over
+    Sequence {
+        channel: Option<u8>,
+        notes: Vec<u8>,
+        timeout_ms: u32,
+    },
 }
 
 impl MidiTrigger {
     pub fn from_message(msg: &MidiMessage) -> Option<Self> {
         match msg {
             MidiMessage::NoteOn(n) => Some(MidiTrigger::NoteOn {
-                channel: n.channel,
+                channel: Some(n.channel),
                 note: n.note,
+                velocity: None,
+                gesture: None,
             }),
             MidiMessage::NoteOff(n) => Some(MidiTrigger::NoteOff {
-                channel: n.channel,
+                channel: Some(n.channel),
                 note: n.note,
             }),
             MidiMessage::ControlChange { channel, cc, .. } => Some(MidiTrigger::ControlChange {
-                channel: *channel,
+                channel: Some(*channel),
                 cc: *cc,
                 value: None,
+                threshold: None,
+            }),
+            MidiMessage::ProgramChange { channel, program } => Some(MidiTrigger::ProgramChange {
+                channel: Some(*channel),
+                program: *program,
             }),
+            MidiMessage::PitchBend { channel, .. } => Some(MidiTrigger::PitchBend {
+                channel: Some(*channel),
+                value: None,
+            }),
+            MidiMessage::ChannelPressure { channel, .. } => Some(MidiTrigger::ChannelPressure {
+                channel: Some(*channel),
+                value: None,
+            }),
+            MidiMessage::PolyAftertouch { channel, note, .. } => Some(MidiTrigger::PolyAftertouch {
+                channel: Some(*channel),
+                note: *note,
+                value: None,
+            }),
+            MidiMessage::SysEx(data) => match MmcCommand::from_sysex(data) {
+                Some(command) => Some(MidiTrigger::Mmc { command }),
+                None => Some(MidiTrigger::SysEx {
+                    pattern: SysExPattern::exact(data),
+                }),
+            },
+            MidiMessage::Cc14 { channel, cc, .. } => Some(MidiTrigger::Cc14 {
+                channel: Some(*channel),
+                cc: *cc,
+                value: None,
+            }),
+            MidiMessage::Nrpn { channel, param, .. } => Some(MidiTrigger::Nrpn {
+                channel: Some(*channel),
+                param: *param,
+                value: None,
+            }),
+            MidiMessage::Rpn { channel, param, .. } => Some(MidiTrigger::Rpn {
+                channel: Some(*channel),
+                param: *param,
+                value: None,
+            }),
+            MidiMessage::MtcQuarterFrame { .. }
+            | MidiMessage::Clock
+            | MidiMessage::Start
+            | MidiMessage::Continue
+            | MidiMessage::Stop => None,
         }
     }
 
     pub fn matches(&self, msg: &MidiMessage) -> bool {
         match (self, msg) {
             (
-                MidiTrigger::NoteOn { channel: c1, note: n1 },
-                MidiMessage::NoteOn(MidiNote { channel: c2, note: n2, .. }),
-            ) => c1 == c2 && n1 == n2,
+                MidiTrigger::NoteOn { channel: c1, note: n1, velocity, .. },
+                MidiMessage::NoteOn(MidiNote { channel: c2, note: n2, velocity: v2 }),
+            ) => on_channel(c1, *c2) && n1 == n2 && ValueRange::allows(velocity, *v2 as u16),
             (
                 MidiTrigger::NoteOff { channel: c1, note: n1 },
                 MidiMessage::NoteOff(MidiNote { channel: c2, note: n2, .. }),
-            ) => c1 == c2 && n1 == n2,
+            ) => on_channel(c1, *c2) && n1 == n2,
             (
-                MidiTrigger::ControlChange { channel: c1, cc: cc1, value },
+                MidiTrigger::ControlChange { channel: c1, cc: cc1, value, threshold },
                 MidiMessage::ControlChange { channel: c2, cc: cc2, value: v2 },
-            ) => c1 == c2 && cc1 == cc2 && value.map_or(true, |v| v == *v2),
+            ) => {
+                // Threshold triggers accept every value; the crossing is decided by the matcher
+                on_channel(c1, *c2)
+                    && cc1 == cc2
+                    && (threshold.is_some() || ValueRange::allows(value, *v2 as u16))
+            }
+            (
+                MidiTrigger::ProgramChange { channel: c1, program: p1 },
+                MidiMessage::ProgramChange { channel: c2, program: p2 },
+            ) => on_channel(c1, *c2) && p1 == p2,
+            (
+                MidiTrigger::PitchBend { channel: c1, value },
+                MidiMessage::PitchBend { channel: c2, value: v2 },
+            ) => on_channel(c1, *c2) && ValueRange::allows(value, *v2),
+            (
+                MidiTrigger::ChannelPressure { channel: c1, value },
+                MidiMessage::ChannelPressure { channel: c2, pressure },
+            ) => on_channel(c1, *c2) && ValueRange::allows(value, *pressure as u16),
+            (
+                MidiTrigger::PolyAftertouch { channel: c1, note: n1, value },
+                MidiMessage::PolyAftertouch { channel: c2, note: n2, pressure },
+            ) => on_channel(c1, *c2) && n1 == n2 && ValueRange::allows(value, *pressure as u16),
+            (MidiTrigger::SysEx { pattern }, MidiMessage::SysEx(data)) => pattern.matches(data),
+            (MidiTrigger::Mmc { command }, MidiMessage::SysEx(data)) => {
+                MmcCommand::from_sysex(data) == Some(*command)
+            }
+            (
+                MidiTrigger::Cc14 { channel: c1, cc: cc1, value },
+                MidiMessage::Cc14 { channel: c2, cc: cc2, value14 },
+            ) => on_channel(c1, *c2) && cc1 == cc2 && ValueRange::allows(value, *value14),
+            (
+                MidiTrigger::Nrpn { channel: c1, param: p1, value },
+                MidiMessage::Nrpn { channel: c2, param: p2, value14 },
+            ) => on_channel(c1, *c2) && p1 == p2 && ValueRange::allows(value, *value14),
+            (
+                MidiTrigger::Rpn { channel: c1, param: p1, value },
+                MidiMessage::Rpn { channel: c2, param: p2, value14 },
+            ) => on_channel(c1, *c2) && p1 == p2 && ValueRange::allows(value, *value14),
+            (
+                MidiTrigger::NoteRange { channel: c1, low, high, velocity, .. },
+                MidiMessage::NoteOn(MidiNote { channel: c2, note, velocity: v2 }),
+            ) => {
+                on_channel(c1, *c2)
+                    && (*low..=*high).contains(note)
+                    && ValueRange::allows(velocity, *v2 as u16)
+            }
+            // Any chord note is a candidate; whether the whole chord is held is up to the matcher
+            (
+                MidiTrigger::Chord { channel: c1, notes, .. },
+                MidiMessage::NoteOn(MidiNote { channel: c2, note, .. }),
+            ) => on_channel(c1, *c2) && notes.contains(note),
+            // Every note on the channel counts, since a wrong one resets the progress
+            (
+                MidiTrigger::Sequence { channel: c1, .. },
+                MidiMessage::NoteOn(MidiNote { channel: c2, .. }),
+            ) => on_channel(c1, *c2),
             _ => false,
         }
     }
 
+    /// Position of the note within a range trigger that runs one action per note
+    pub fn action_position(&self, msg: &MidiMessage) -> Option<usize> {
+        match (self, msg) {
+            (
+                MidiTrigger::NoteRange { low, action_per_note: true, .. },
+                MidiMessage::NoteOn(n),
+            ) if self.matches(msg) => Some((n.note - low) as usize),
+            _ => None,
+        }
+    }
+
+    /// Channel filter of channel voice triggers, `None` for SysEx based triggers
+    pub fn channel_mut(&mut self) -> Option<&mut Option<u8>> {
+        match self {
+            MidiTrigger::NoteOn { channel, .. }
+            | MidiTrigger::NoteOff { channel, .. }
+            | MidiTrigger::ControlChange { channel, .. }
+            | MidiTrigger::ProgramChange { channel, .. }
+            | MidiTrigger::PitchBend { channel, .. }
+            | MidiTrigger::ChannelPressure { channel, .. }
+            | MidiTrigger::PolyAftertouch { channel, .. }
+            | MidiTrigger::Cc14 { channel, .. }
+            | MidiTrigger::Nrpn { channel, .. }
+            | MidiTrigger::Rpn { channel, .. }
+            | MidiTrigger::NoteRange { channel, .. }
+            | MidiTrigger::Chord { channel, .. }
+            | MidiTrigger::Sequence { channel, .. } => Some(channel),
+            MidiTrigger::SysEx { .. } | MidiTrigger::Mmc { .. } => None,
+        }
+    }
+
     pub fn display_name(&self) -> String {
         match self {
-            MidiTrigger::NoteOn { channel, note } => {
-                format!("Note On Ch{} N{} ({})", channel, note, note_name(*note))
+            MidiTrigger::NoteOn { channel, note, velocity, gesture } => {
+                let mut name = format!(
+                    "Note On {} N{} ({})",
+                    channel_label(channel),
+                    note,
+                    note_name(*note)
+                );
+                if let Some(range) = velocity {
+                    name = format!("{} vel {}", name, range);
+                }
+                if let Some(gesture) = gesture {
+                    name = format!("{} [{}]", name, gesture.name());
+                }
+                name
             }
             MidiTrigger::NoteOff { channel, note } => {
-                format!("Note Off Ch{} N{} ({})", channel, note, note_name(*note))
+                format!("Note Off {} N{} ({})", channel_label(channel), note, note_name(*note))
             }
-            MidiTrigger::ControlChange { channel, cc, value } => {
-                if let Some(v) = value {
-                    format!("CC{} Ch{} = {}", cc, channel, v)
+            MidiTrigger::ControlChange { channel, cc, value, threshold } => {
+                let channel = channel_label(channel);
+                if let Some(t) = threshold {
+                    format!("CC{} {} {}", cc, channel, t)
+                } else if let Some(v) = value {
+                    format!("CC{} {} = {}", cc, channel, v)
                 } else {
-                    format!("CC{} Ch{} (any)", cc, channel)
+                    format!("CC{} {} (any)", cc, channel)
                 }
             }
+            MidiTrigger::ProgramChange { channel, program } => {
+                format!("Program Change {} P{}", channel_label(channel), program)
+            }
+            MidiTrigger::PitchBend { channel, value } => {
+                format!("Pitch Bend {} {}", channel_label(channel), range_suffix(value))
+            }
+            MidiTrigger::ChannelPressure { channel, value } => {
+                format!("Channel Pressure {} {}", channel_label(channel), range_suffix(value))
+            }
+            MidiTrigger::PolyAftertouch { channel, note, value } => format!(
+                "Poly Aftertouch {} N{} ({}) {}",
+                channel_label(channel),
+                note,
+                note_name(*note),
+                range_suffix(value)
+            ),
+            MidiTrigger::SysEx { pattern } => format!("SysEx {}", pattern),
+            MidiTrigger::Mmc { command } => format!("MMC {}", command.name()),
+            MidiTrigger::Cc14 { channel, cc, value } => format!(
+                "14-bit CC{} {} {}",
+                cc,
+                channel_label(channel),
+                range_suffix(value)
+            ),
+            MidiTrigger::Nrpn { channel, param, value } => format!(
+                "NRPN {} {} {}",
+                param,
+                channel_label(channel),
+                range_suffix(value)
+            ),
+            MidiTrigger::Rpn { channel, param, value } => format!(
+                "RPN {} {} {}",
+                param,
+                channel_label(channel),
+                range_suffix(value)
+            ),
+            MidiTrigger::NoteRange { channel, low, high, velocity, action_per_note } => {
+                let mut name = format!(
+                    "Note On {} N{}-N{} ({}{}-{}{})",
+                    channel_label(channel),
+                    low,
+                    high,
+                    note_name(*low),
+                    note_octave(*low),
+                    note_name(*high),
+                    note_octave(*high)
+                );
+                if let Some(range) = velocity {
+                    name = format!("{} vel {}", name, range);
+                }
+                if *action_per_note {
+                    name.push_str(" [action per note]");
+                }
+                name
+            }
+            MidiTrigger::Chord { channel, notes, window_ms } => {
+                let notes: Vec<String> = notes
+                    .iter()
+                    .map(|n| format!("N{} ({}{})", n, note_name(*n), note_octave(*n)))
+                    .collect();
+                format!(
+                    "Chord {} {} within {}ms",
+                    channel_label(channel),
+                    notes.join(" + "),
+                    window_ms
+                )
+            }
+            MidiTrigger::Sequence { channel, notes, timeout_ms } => {
+                let notes: Vec<String> = notes
+                    .iter()
+                    .map(|n| format!("N{} ({}{})", n, note_name(*n), note_octave(*n)))
+                    .collect();
+                format!(
+                    "Sequence {} {} within {}ms",
+                    channel_label(channel),
+                    notes.join(" > "),
+                    timeout_ms
+                )
+            }
         }
     }
 }
 
+/// MIDI Machine Control transport commands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MmcCommand {
+    Stop,
+    Play,
+    DeferredPlay,
+    RecordStrobe,
+    Locate,
+    Pause,
+}
+
+impl MmcCommand {
+    /// Decode an MMC command from a `F0 7F <device> 06 <command> ... F7` message
+    pub fn from_sysex(data: &[u8]) -> Option<Self> {
+        match data {
+            [0xF0, 0x7F, _device, 0x06, command, .., 0xF7] => match command {
+                0x01 => Some(MmcCommand::Stop),
+                0x02 => Some(MmcCommand::Play),
+                0x03 => Some(MmcCommand::DeferredPlay),
+                0x06 => Some(MmcCommand::RecordStrobe),
+                0x09 => Some(MmcCommand::Pause),
+                0x44 => Some(MmcCommand::Locate),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MmcCommand::Stop => "Stop",
+            MmcCommand::Play => "Play",
+            MmcCommand::DeferredPlay => "Deferred Play",
+            MmcCommand::RecordStrobe => "Record Strobe",
+            MmcCommand::Locate => "Locate",
+            MmcCommand::Pause => "Pause",
+        }
+    }
+}
+
+/// Inclusive range of data values a trigger accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValueRange {
+    pub min: u16,
+    pub max: u16,
+}
+
+impl ValueRange {
+    pub fn new(min: u16, max: u16) -> Self {
+        Self {
+            min: min.min(max),
+            max: min.max(max),
+        }
+    }
+
+    pub fn contains(&self, value: u16) -> bool {
+        (self.min..=self.max).contains(&value)
+    }
+
+    /// An unset range accepts every value
+    pub fn allows(range: &Option<ValueRange>, value: u16) -> bool {
+        match range {
+            Some(r) => r.contains(value),
+            None => true,
+        }
+    }
+}
+
+impl std::fmt::Display for ValueRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.min == self.max {
+            write!(f, "{}", self.min)
+        } else {
+            write!(f, "{}-{}", self.min, self.max)
+        }
+    }
+}
+
+/// Direction of a threshold crossing that fires a CC trigger
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CrossingDirection {
+    Rising,
+    Falling,
+    Either,
+}
+
+impl CrossingDirection {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CrossingDirection::Rising => "Rising",
+            CrossingDirection::Falling => "Falling",
+            CrossingDirection::Either => "Either",
+        }
+    }
+}
+
+/// How a pad has to be played for a note trigger to fire
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NoteGesture {
+    /// Pressed and released, with no second press inside the double tap window
+    Tap,
+    /// Two taps within the double tap window
+    DoubleTap,
+    /// Held down for the long press time; fires while the note is still held
+    LongPress,
+}
+
+impl NoteGesture {
+    pub fn name(&self) -> &'static str {
+        match self {
+            NoteGesture::Tap => "Tap",
+            NoteGesture::DoubleTap => "Double Tap",
+            NoteGesture::LongPress => "Long Press",
+        }
+    }
+}
+
+/// Timing used to tell gestures apart, shared by all note triggers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GestureTimings {
+    pub double_tap_ms: u32,
+    pub long_press_ms: u32,
+}
+
+impl Default for GestureTimings {
+    fn default() -> Self {
+        Self {
+            double_tap_ms: 300,
+            long_press_ms: 600,
+        }
+    }
+}
+
+/// Where the tempo reported to the controller comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum TempoSource {
+    #[default]
+    Manual,
+    /// The last BPM ShowXpress reported
+    Controller,
+    TapTempo,
+    MidiClock,
+}
+
+impl TempoSource {
+    pub const ALL: [TempoSource; 4] = [
+        TempoSource::Manual,
+        TempoSource::Controller,
+        TempoSource::TapTempo,
+        TempoSource::MidiClock,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TempoSource::Manual => "Manual",
+            TempoSource::Controller => "Controller",
+            TempoSource::TapTempo => "Tap Tempo",
+            TempoSource::MidiClock => "MIDI Clock",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TempoSettings {
+    pub source: TempoSource,
+    /// Used by the manual source, and by the others until they have a reading
+    pub manual_bpm: f32,
+}
+
+impl Default for TempoSettings {
+    fn default() -> Self {
+        Self {
+            source: TempoSource::Manual,
+            manual_bpm: 120.0,
+        }
+    }
+}
+
+/// How the controller link is checked. Each heartbeat is a request that has
+/// to be answered within `timeout_ms`; after `max_misses` unanswered ones in
+/// a row the link is treated as dead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeartbeatSettings {
+    pub interval_ms: u32,
+    pub timeout_ms: u32,
+    pub max_misses: u32,
+}
+
+impl Default for HeartbeatSettings {
+    fn default() -> Self {
+        Self {
+            interval_ms: 3000,
+            timeout_ms: 1500,
+            max_misses: 3,
+        }
+    }
+}
+
+/// Edge-triggered CC condition. After a crossing the value has to move back
+/// past `level` by more than `hysteresis` before the trigger re-arms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CcThreshold {
+    pub level: u8,
+    pub direction: CrossingDirection,
+    pub hysteresis: u8,
+}
+
+impl CcThreshold {
+    /// Update the above/below state for a new value, returning true on a
+    /// crossing in the configured direction. The first value only sets the state.
+    pub fn update(&self, above: &mut Option<bool>, value: u8) -> bool {
+        let was_above = match *above {
+            Some(was_above) => was_above,
+            None => {
+                *above = Some(value >= self.level);
+                return false;
+            }
+        };
+
+        let is_above = if was_above {
+            value >= self.level.saturating_sub(self.hysteresis)
+        } else {
+            value >= self.level.saturating_add(self.hysteresis)
+        };
+        *above = Some(is_above);
+
+        match (was_above, is_above) {
+            (false, true) => self.direction != CrossingDirection::Falling,
+            (true, false) => self.direction != CrossingDirection::Rising,
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for CcThreshold {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "crosses {} ({}", self.level, self.direction.name().to_lowercase())?;
+        if self.hysteresis > 0 {
+            write!(f, ", ±{}", self.hysteresis)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// SysEx byte pattern where `None` positions match any byte
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SysExPattern(pub Vec<Option<u8>>);
+
+impl SysExPattern {
+    /// Pattern that only matches exactly these bytes
+    pub fn exact(data: &[u8]) -> Self {
+        Self(data.iter().copied().map(Some).collect())
+    }
+
+    /// Parse a pattern like `F0 7F ?? 06 02 F7`
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let bytes = text
+            .split_whitespace()
+            .map(|token| {
+                if token == "??" || token.eq_ignore_ascii_case("xx") {
+                    Ok(None)
+                } else {
+                    u8::from_str_radix(token, 16)
+                        .map(Some)
+                        .map_err(|_| format!("Invalid SysEx byte: {}", token))
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        match (bytes.first(), bytes.last()) {
+            (Some(Some(0xF0)), Some(Some(0xF7))) => Ok(Self(bytes)),
+            _ => Err("SysEx pattern must start with F0 and end with F7".to_string()),
+        }
+    }
+
+    pub fn matches(&self, data: &[u8]) -> bool {
+        self.0.len() == data.len()
+            && self
+                .0
+                .iter()
+                .zip(data)
+                .all(|(expected, byte)| expected.is_none() || *expected == Some(*byte))
+    }
+}
+
+impl std::fmt::Display for SysExPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let tokens: Vec<String> = self
+            .0
+            .iter()
+            .map(|b| match b {
+                Some(b) => format!("{:02X}", b),
+                None => "??".to_string(),
+            })
+            .collect();
+        write!(f, "{}", tokens.join(" "))
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ButtonActionType {
     Press,
@@ -99,13 +747,251 @@ pub enum ButtonActionType {
     Toggle,
 }
 
+impl ButtonActionType {
+    /// The action that undoes this one; a toggle undoes itself
+    pub fn mirrored(self) -> Self {
+        match self {
+            ButtonActionType::Press => ButtonActionType::Release,
+            ButtonActionType::Release => ButtonActionType::Press,
+            ButtonActionType::Toggle => ButtonActionType::Toggle,
+        }
+    }
+}
+
+/// Shape applied to a 0.0-1.0 position before it is scaled to a fader value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ResponseCurve {
+    #[default]
+    Linear,
+    /// Slow at both ends, fast in the middle
+    SCurve,
+    /// Fine control at the bottom of the travel
+    Exponential,
+    /// Fine control at the top of the travel
+    Logarithmic,
+}
+
+impl ResponseCurve {
+    pub const ALL: [ResponseCurve; 4] = [
+        ResponseCurve::Linear,
+        ResponseCurve::SCurve,
+        ResponseCurve::Exponential,
+        ResponseCurve::Logarithmic,
+    ];
+
+    pub fn apply(&self, x: f32) -> f32 {
+        let x = x.clamp(0.0, 1.0);
+        match self {
+            ResponseCurve::Linear => x,
+            ResponseCurve::SCurve => x * x * (3.0 - 2.0 * x),
+            ResponseCurve::Exponential => (10f32.powf(x) - 1.0) / 9.0,
+            ResponseCurve::Logarithmic => (1.0 + 9.0 * x).log10(),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ResponseCurve::Linear => "Linear",
+            ResponseCurve::SCurve => "S-Curve",
+            ResponseCurve::Exponential => "Exponential",
+            ResponseCurve::Logarithmic => "Logarithmic",
+        }
+    }
+}
+
+/// Continuously drives a controller fader from a CC or 14-bit CC. Mappings are
+/// sent straight to the controller and never go through the preset queue.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FaderMapping {
+    pub channel: Option<u8>,
+    pub cc: u8,
+    /// Follow the combined 14-bit value of `cc` and `cc + 32` instead of the plain CC
+    pub high_res: bool,
+    pub fader: u32,
+    /// Fader value sent at the bottom of the MIDI range
+    pub out_min: i32,
+    /// Fader value sent at the top of the MIDI range
+    pub out_max: i32,
+    pub invert: bool,
+    pub curve: ResponseCurve,
+}
+
+impl FaderMapping {
+    pub fn new(channel: Option<u8>, cc: u8, high_res: bool) -> Self {
+        Self {
+            channel,
+            cc,
+            high_res,
+            fader: 0,
+            out_min: 0,
+            out_max: 255,
+            invert: false,
+            curve: ResponseCurve::Linear,
+        }
+    }
+
+    /// Fader value for a message this mapping follows
+    pub fn value_for(&self, msg: &MidiMessage) -> Option<i32> {
+        let position = match (msg, self.high_res) {
+            (MidiMessage::ControlChange { channel, cc, value }, false)
+                if *cc == self.cc && on_channel(&self.channel, *channel) =>
+            {
+                *value as f32 / 127.0
+            }
+            (MidiMessage::Cc14 { channel, cc, value14 }, true)
+                if *cc == self.cc && on_channel(&self.channel, *channel) =>
+            {
+                *value14 as f32 / 16383.0
+            }
+            _ => return None,
+        };
+
+        let position = if self.invert { 1.0 - position } else { position };
+        let shaped = self.curve.apply(position);
+        let range = (self.out_max - self.out_min) as f32;
+        Some(self.out_min + (shaped * range).round() as i32)
+    }
+
+    pub fn display_name(&self) -> String {
+        let source = if self.high_res {
+            format!("14-bit CC{}", self.cc)
+        } else {
+            format!("CC{}", self.cc)
+        };
+        let mut name = format!(
+            "{} {} -> Fader {} ({}-{}, {})",
+            source,
+            channel_label(&self.channel),
+            self.fader,
+            self.out_min,
+            self.out_max,
+            self.curve.name()
+        );
+        if self.invert {
+            name.push_str(" inverted");
+        }
+        name
+    }
+}
+
+/// A command sent to the lighting controller as part of a preset
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ControllerAction {
+    Button {
+        button_name: String, // now the primary identifier
+        action: ButtonActionType,
+    },
+    Bpm { bpm: f32 },
+    Beat,
+    /// Ramp a fader to `to` over `duration_secs`, starting from `from` or the
+    /// last value sent to the fader
+    Fade {
+        fader: u32,
+        from: Option<i32>,
+        to: i32,
+        duration_secs: f32,
+        curve: ResponseCurve,
+        /// Fader updates sent per second
+        rate_hz: u32,
+    },
+    FreezeOn,
+    FreezeOff,
+    AutoBpmOn,
+    AutoBpmOff,
+}
+
+impl ControllerAction {
+    /// Every controller command that isn't tied to a button, with default parameters
+    pub fn controller_commands() -> [ControllerAction; 7] {
+        [
+            ControllerAction::Bpm { bpm: 120.0 },
+            ControllerAction::Beat,
+            ControllerAction::Fade {
+                fader: 0,
+                from: None,
+                to: 0,
+                duration_secs: 3.0,
+                curve: ResponseCurve::Linear,
+                rate_hz: 30,
+            },
+            ControllerAction::FreezeOn,
+            ControllerAction::FreezeOff,
+            ControllerAction::AutoBpmOn,
+            ControllerAction::AutoBpmOff,
+        ]
+    }
+
+    pub fn button_name(&self) -> Option<&str> {
+        match self {
+            ControllerAction::Button { button_name, .. } => Some(button_name),
+            _ => None,
+        }
+    }
+
+    /// The action that undoes this one, if there is one
+    pub fn mirrored(&self) -> Option<Self> {
+        match self {
+            ControllerAction::Button { button_name, action } => Some(ControllerAction::Button {
+                button_name: button_name.clone(),
+                action: action.mirrored(),
+            }),
+            ControllerAction::FreezeOn => Some(ControllerAction::FreezeOff),
+            ControllerAction::FreezeOff => Some(ControllerAction::FreezeOn),
+            ControllerAction::AutoBpmOn => Some(ControllerAction::AutoBpmOff),
+            ControllerAction::AutoBpmOff => Some(ControllerAction::AutoBpmOn),
+            ControllerAction::Bpm { .. } | ControllerAction::Beat | ControllerAction::Fade { .. } => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ControllerAction::Button { .. } => "Button",
+            ControllerAction::Bpm { .. } => "Set BPM",
+            ControllerAction::Beat => "Beat",
+            ControllerAction::Fade { .. } => "Fade",
+            ControllerAction::FreezeOn => "Freeze On",
+            ControllerAction::FreezeOff => "Freeze Off",
+            ControllerAction::AutoBpmOn => "Auto BPM On",
+            ControllerAction::AutoBpmOff => "Auto BPM Off",
+        }
+    }
+
+    pub fn display_name(&self) -> String {
+        match self {
+            ControllerAction::Button { button_name, action } => format!("{:?} {}", action, button_name),
+            ControllerAction::Bpm { bpm } => format!("Set BPM {:.1}", bpm),
+            ControllerAction::Fade { fader, from, to, duration_secs, curve, .. } => {
+                let from = from.map(|v| format!(" from {}", v)).unwrap_or_default();
+                format!(
+                    "Fade Fader {}{} to {} over {:.1}s ({})",
+                    fader,
+                    from,
+                    to,
+                    duration_secs,
+                    curve.name()
+                )
+            }
+            _ => self.name().to_string(),
+        }
+    }
+}
+
+/// One step of a preset, sent `delay_secs` after the previous one
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ButtonAction {
-    pub button_name: String,  // now the primary identifier
-    pub action: ButtonActionType,
+pub struct PresetAction {
+    pub command: ControllerAction,
     pub delay_secs: f32,
 }
 
+impl PresetAction {
+    pub fn button(button_name: String, action: ButtonActionType) -> Self {
+        Self {
+            command: ControllerAction::Button { button_name, action },
+            delay_secs: 0.0,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct MidiNote {
     pub channel: u8,
@@ -118,18 +1004,59 @@ pub enum MidiMessage {
     NoteOn(MidiNote),
     NoteOff(MidiNote),
     ControlChange { channel: u8, cc: u8, value: u8 },
+    ProgramChange { channel: u8, program: u8 },
+    /// 14-bit bend value, 0..=16383 with 8192 as center
+    PitchBend { channel: u8, value: u16 },
+    ChannelPressure { channel: u8, pressure: u8 },
+    PolyAftertouch { channel: u8, note: u8, pressure: u8 },
+    /// Complete System Exclusive message including the F0/F7 framing
+    SysEx(Vec<u8>),
+    /// MTC quarter frame: piece index (0-7) and its 4-bit value
+    MtcQuarterFrame { piece: u8, value: u8 },
+    /// MIDI Clock pulse, 24 per quarter note
+    Clock,
+    Start,
+    Continue,
+    Stop,
+    /// 14-bit controller assembled from an MSB/LSB CC pair (cc is the MSB number, 0-31)
+    Cc14 { channel: u8, cc: u8, value14: u16 },
+    Nrpn { channel: u8, param: u16, value14: u16 },
+    Rpn { channel: u8, param: u16, value14: u16 },
 }
 
 impl MidiMessage {
     pub fn from_raw(data: &[u8]) -> Option<Self> {
-        if data.len() < 3 {
-            return None;
+        let status = *data.first()?;
+        if status == 0xF0 {
+            return Some(MidiMessage::SysEx(data.to_vec()));
+        }
+        match status {
+            0xF8 => return Some(MidiMessage::Clock),
+            0xFA => return Some(MidiMessage::Start),
+            0xFB => return Some(MidiMessage::Continue),
+            0xFC => return Some(MidiMessage::Stop),
+            _ => {}
+        }
+        if status == 0xF1 {
+            let data = *data.get(1)?;
+            return Some(MidiMessage::MtcQuarterFrame {
+                piece: (data >> 4) & 0x07,
+                value: data & 0x0F,
+            });
         }
 
-        let status = data[0];
         let message_type = status & 0xF0;
         let channel = status & 0x0F;
 
+        // Program Change and Channel Pressure carry a single data byte
+        let expected_len = match message_type {
+            0xC0 | 0xD0 => 2,
+            _ => 3,
+        };
+        if data.len() < expected_len {
+            return None;
+        }
+
         match message_type {
             0x90 => {
                 let velocity = data[2];
@@ -157,10 +1084,74 @@ impl MidiMessage {
                 cc: data[1],
                 value: data[2],
             }),
+            0xA0 => Some(MidiMessage::PolyAftertouch {
+                channel,
+                note: data[1],
+                pressure: data[2],
+            }),
+            0xC0 => Some(MidiMessage::ProgramChange {
+                channel,
+                program: data[1],
+            }),
+            0xD0 => Some(MidiMessage::ChannelPressure {
+                channel,
+                pressure: data[1],
+            }),
+            0xE0 => Some(MidiMessage::PitchBend {
+                channel,
+                value: (data[1] as u16 & 0x7F) | ((data[2] as u16 & 0x7F) << 7),
+            }),
             _ => None,
         }
     }
 
+    /// Category used to group messages in the MIDI Monitor tree
+    pub fn category(&self) -> &'static str {
+        match self {
+            MidiMessage::NoteOn(_) => "Note On",
+            MidiMessage::NoteOff(_) => "Note Off",
+            MidiMessage::ControlChange { .. } => "Control Change",
+            MidiMessage::ProgramChange { .. } => "Program Change",
+            MidiMessage::PitchBend { .. } => "Pitch Bend",
+            MidiMessage::ChannelPressure { .. } => "Channel Pressure",
+            MidiMessage::PolyAftertouch { .. } => "Poly Aftertouch",
+            MidiMessage::SysEx(data) if MmcCommand::from_sysex(data).is_some() => "MIDI Machine Control",
+            MidiMessage::SysEx(data) if Timecode::from_full_frame(data).is_some() => "MIDI Timecode",
+            MidiMessage::SysEx(_) => "SysEx",
+            MidiMessage::MtcQuarterFrame { .. } => "MIDI Timecode",
+            MidiMessage::Clock => "MIDI Clock",
+            MidiMessage::Start | MidiMessage::Continue | MidiMessage::Stop => "Transport",
+            MidiMessage::Cc14 { .. } => "14-bit Control Change",
+            MidiMessage::Nrpn { .. } => "NRPN",
+            MidiMessage::Rpn { .. } => "RPN",
+        }
+    }
+
+    /// Identifies the control a message comes from, leaving out its value, so
+    /// the monitor keeps one entry per note, controller or parameter
+    pub fn monitor_key(&self) -> String {
+        match self {
+            MidiMessage::NoteOn(n) => format!("Note On Ch{} N{}", n.channel, n.note),
+            MidiMessage::NoteOff(n) => format!("Note Off Ch{} N{}", n.channel, n.note),
+            MidiMessage::ControlChange { channel, cc, .. } => format!("CC{} Ch{}", cc, channel),
+            MidiMessage::PitchBend { channel, .. } => format!("Pitch Bend Ch{}", channel),
+            MidiMessage::ChannelPressure { channel, .. } => format!("Channel Pressure Ch{}", channel),
+            MidiMessage::PolyAftertouch { channel, note, .. } => {
+                format!("Poly Aftertouch Ch{} N{}", channel, note)
+            }
+            MidiMessage::MtcQuarterFrame { piece, .. } => format!("MTC Quarter Frame {}", piece),
+            MidiMessage::Cc14 { channel, cc, .. } => format!("14-bit CC{} Ch{}", cc, channel),
+            MidiMessage::Nrpn { channel, param, .. } => format!("NRPN {} Ch{}", param, channel),
+            MidiMessage::Rpn { channel, param, .. } => format!("RPN {} Ch{}", param, channel),
+            _ => self.display_name(),
+        }
+    }
+
+    /// High-rate timing messages that would flood the monitor and log
+    pub fn is_timing(&self) -> bool {
+        matches!(self, MidiMessage::MtcQuarterFrame { .. } | MidiMessage::Clock)
+    }
+
     pub fn display_name(&self) -> String {
         match self {
             MidiMessage::NoteOn(n) => format!("Note On Ch{} N{} V{}", n.channel, n.note, n.velocity),
@@ -170,6 +1161,43 @@ impl MidiMessage {
             MidiMessage::ControlChange { channel, cc, value } => {
                 format!("CC{} Ch{} = {}", cc, channel, value)
             }
+            MidiMessage::ProgramChange { channel, program } => {
+                format!("Program Change Ch{} P{}", channel, program)
+            }
+            MidiMessage::PitchBend { channel, value } => {
+                format!("Pitch Bend Ch{} = {}", channel, value)
+            }
+            MidiMessage::ChannelPressure { channel, pressure } => {
+                format!("Channel Pressure Ch{} = {}", channel, pressure)
+            }
+            MidiMessage::PolyAftertouch { channel, note, pressure } => {
+                format!("Poly Aftertouch Ch{} N{} = {}", channel, note, pressure)
+            }
+            MidiMessage::SysEx(data) => {
+                if let Some(command) = MmcCommand::from_sysex(data) {
+                    format!("MMC {} ({})", command.name(), hex_bytes(data))
+                } else if let Some((timecode, rate)) = Timecode::from_full_frame(data) {
+                    format!("MTC Full Frame {} @ {}", timecode, rate.name())
+                } else {
+                    format!("SysEx {}", hex_bytes(data))
+                }
+            }
+            MidiMessage::MtcQuarterFrame { piece, value } => {
+                format!("MTC Quarter Frame {} = {:X}", piece, value)
+            }
+            MidiMessage::Clock => "Clock".to_string(),
+            MidiMessage::Start => "Start".to_string(),
+            MidiMessage::Continue => "Continue".to_string(),
+            MidiMessage::Stop => "Stop".to_string(),
+            MidiMessage::Cc14 { channel, cc, value14 } => {
+                format!("14-bit CC{} Ch{} = {}", cc, channel, value14)
+            }
+            MidiMessage::Nrpn { channel, param, value14 } => {
+                format!("NRPN {} Ch{} = {}", param, channel, value14)
+            }
+            MidiMessage::Rpn { channel, param, value14 } => {
+                format!("RPN {} Ch{} = {}", param, channel, value14)
+            }
         }
     }
 }
@@ -203,7 +1231,40 @@ impl MidiLearnState {
     }
 }
 
-fn note_name(note: u8) -> &'static str {
+fn on_channel(filter: &Option<u8>, channel: u8) -> bool {
+    match filter {
+        Some(c) => *c == channel,
+        None => true,
+    }
+}
+
+fn channel_label(filter: &Option<u8>) -> String {
+    match filter {
+        Some(c) => format!("Ch{}", c),
+        None => "Ch Any".to_string(),
+    }
+}
+
+fn range_suffix(range: &Option<ValueRange>) -> String {
+    match range {
+        Some(range) => format!("= {}", range),
+        None => "(any)".to_string(),
+    }
+}
+
+fn hex_bytes(data: &[u8]) -> String {
+    data.iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Octave number with middle C (note 60) as C3
+pub fn note_octave(note: u8) -> i8 {
+    (note / 12) as i8 - 2
+}
+
+pub fn note_name(note: u8) -> &'static str {
     const NAMES: [&str; 12] =
         ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
     NAMES[(note % 12) as usize]
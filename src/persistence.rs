@@ -9,6 +9,7 @@ use crate::versioned_data::{load_presets, load_config, save_presets, save_config
 pub struct PresetStorage {
     file_path: PathBuf,
     config_path: PathBuf,
+    history_path: PathBuf,
 }
 
 impl PresetStorage {
@@ -21,8 +22,14 @@ impl PresetStorage {
 
         let file_path = config_dir.join("presets.json");
         let config_path = config_dir.join("config.json");
+        let history_path = config_dir.join("history.jsonl");
 
-        Ok(Self { file_path, config_path })
+        Ok(Self { file_path, config_path, history_path })
+    }
+
+    /// Execution history file, kept next to the presets
+    pub fn history_path(&self) -> PathBuf {
+        self.history_path.clone()
     }
 
     pub fn load(&self) -> Result<Vec<Preset>> {
@@ -104,6 +111,18 @@ pub struct AppConfig {
     pub last_controller_address: Option<String>,
     pub last_controller_password: Option<String>,
     pub last_action_type: Option<crate::models::ButtonActionType>,
+    #[serde(default)]
+    pub timecode_cue_list: bool,
+    #[serde(default)]
+    pub tap_tempo_trigger: Option<crate::models::MidiTrigger>,
+    #[serde(default)]
+    pub gesture_timings: crate::models::GestureTimings,
+    #[serde(default)]
+    pub fader_mappings: Vec<crate::models::FaderMapping>,
+    #[serde(default)]
+    pub heartbeat: crate::models::HeartbeatSettings,
+    #[serde(default)]
+    pub tempo: crate::models::TempoSettings,
 }
 
 impl Default for AppConfig {
@@ -113,6 +132,12 @@ impl Default for AppConfig {
             last_controller_address: Some("127.0.0.1:7348".to_string()),
             last_controller_password: None,
             last_action_type: Some(crate::models::ButtonActionType::Toggle),
+            timecode_cue_list: false,
+            tap_tempo_trigger: None,
+            gesture_timings: crate::models::GestureTimings::default(),
+            fader_mappings: Vec::new(),
+            heartbeat: crate::models::HeartbeatSettings::default(),
+            tempo: crate::models::TempoSettings::default(),
         }
     }
 }
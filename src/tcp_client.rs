@@ -1,14 +1,30 @@
 use anyhow::{anyhow, Result};
 use roxmltree::Document;
 use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Instant;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
 use tokio::net::TcpStream;
+use tokio::sync::{broadcast, oneshot, watch, Mutex};
+use tokio::task::JoinHandle;
 use tokio::time::{timeout, Duration};
 
-use crate::models::Button;
+use crate::models::{Button, TempoSource};
+use crate::tempo::SharedTempo;
+
+/// Messages a subscriber can fall behind by before it starts missing them
+const MESSAGE_BUFFER: usize = 256;
+
+/// How long to wait for the TCP connection, so an unreachable host fails
+/// fast enough for the reconnect backoff to keep its schedule
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a request keeps its place in line for a reply that never comes
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
 
 /// All messages TLC can send to the external client
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum LiveMessage {
     Connected(String),
     Error(String),
@@ -160,16 +176,96 @@ fn parse_buttons(xml: &[u8]) -> Vec<Button> {
         .collect()
 }
 
-/// TCP client for Lighting Controller
+/// Replies a request can wait for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReplyKind {
+    ButtonList,
+}
+
+impl ReplyKind {
+    fn of(msg: &LiveMessage) -> Option<Self> {
+        match msg {
+            LiveMessage::ButtonList(_) => Some(ReplyKind::ButtonList),
+            _ => None,
+        }
+    }
+}
+
+/// A request waiting for its reply
+struct PendingReply {
+    id: u64,
+    kind: ReplyKind,
+    sent: Instant,
+    reply: oneshot::Sender<LiveMessage>,
+}
+
+/// Requests waiting for a reply. Each kind of reply is answered in the order
+/// its requests were sent; other messages, `ERROR` included, are never routed
+/// since nothing ties them to a request.
+#[derive(Default)]
+struct PendingState {
+    next_id: u64,
+    waiting: VecDeque<PendingReply>,
+}
+
+impl PendingState {
+    fn push(&mut self, kind: ReplyKind, reply: oneshot::Sender<LiveMessage>) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.waiting.push_back(PendingReply {
+            id,
+            kind,
+            sent: Instant::now(),
+            reply,
+        });
+        id
+    }
+
+    fn remove(&mut self, id: u64) {
+        self.waiting.retain(|pending| pending.id != id);
+    }
+
+    /// Hand `msg` to the oldest request waiting for its kind of reply. A late
+    /// reply to a request that gave up waiting is dropped rather than handed
+    /// to the next one.
+    fn answer(&mut self, msg: &LiveMessage) {
+        let Some(kind) = ReplyKind::of(msg) else {
+            return;
+        };
+        // A request the server never answered would otherwise take every later reply
+        self.waiting.retain(|pending| pending.sent.elapsed() < REPLY_TIMEOUT);
+        if let Some(i) = self.waiting.iter().position(|pending| pending.kind == kind) {
+            if let Some(pending) = self.waiting.remove(i) {
+                let _ = pending.reply.send(msg.clone());
+            }
+        }
+    }
+}
+
+type PendingReplies = Arc<std::sync::Mutex<PendingState>>;
+
+/// TCP client for Lighting Controller.
+///
+/// The socket is split in two: commands are written from the client, while a
+/// background task reads everything TLC sends. The reader hands replies such
+/// as `BUTTON_LIST` to the request waiting for them and publishes every
+/// message, solicited or not, to subscribers.
 pub struct LightingControllerClient {
-    stream: TcpStream,
-    parser: LiveParser,
+    writer: Arc<Mutex<OwnedWriteHalf>>,
+    messages: broadcast::Sender<LiveMessage>,
+    pending: PendingReplies,
+    reader: JoinHandle<()>,
+    /// Changes once the reader stops, when its sender is dropped
+    closed: watch::Receiver<()>,
 }
 
 impl LightingControllerClient {
-    /// Connect and perform HELLO handshake
-    pub async fn connect(addr: &str, password: &str) -> Result<Self> {
-        let mut stream = TcpStream::connect(addr).await?;
+    /// Connect and perform HELLO handshake. BPM requests from TLC are
+    /// answered from `tempo`.
+    pub async fn connect(addr: &str, password: &str, tempo: SharedTempo) -> Result<Self> {
+        let mut stream = timeout(CONNECT_TIMEOUT, TcpStream::connect(addr))
+            .await
+            .map_err(|_| anyhow!("No answer from {} within {} s", addr, CONNECT_TIMEOUT.as_secs()))??;
         let mut parser = LiveParser::new();
 
         // Send HELLO immediately
@@ -194,35 +290,60 @@ impl LightingControllerClient {
             }
         }
 
-        Ok(Self { stream, parser })
+        let (read_half, write_half) = stream.into_split();
+        let writer = Arc::new(Mutex::new(write_half));
+        let (messages, _) = broadcast::channel(MESSAGE_BUFFER);
+        let pending = PendingReplies::default();
+        let (closed_tx, closed) = watch::channel(());
+
+        // Anything that arrived right after HELLO is still in the parser
+        let reader = tokio::spawn(read_loop(
+            read_half,
+            parser,
+            Arc::clone(&writer),
+            messages.clone(),
+            Arc::clone(&pending),
+            tempo,
+            closed_tx,
+        ));
+
+        Ok(Self {
+            writer,
+            messages,
+            pending,
+            reader,
+            closed,
+        })
     }
 
-    async fn send(&mut self, cmd: &str) -> Result<()> {
-        self.stream
-            .write_all(format!("{}\r\n", cmd).as_bytes())
-            .await?;
-        Ok(())
+    /// Resolves once the socket has closed and the reader has stopped
+    pub fn closed(&self) -> impl std::future::Future<Output = ()> + Send + 'static {
+        let mut closed = self.closed.clone();
+        async move {
+            // Only ever errors, when the reader drops its sender
+            let _ = closed.changed().await;
+        }
     }
 
-    /// Read next parsed message from TLC
-    pub async fn read_message(&mut self) -> Result<LiveMessage> {
-        loop {
-            if let Some(msg) = self.parser.next_message() {
-                 // If server is asking for BPM, reply with default 120 if not set
-                if let LiveMessage::Bpm(_) = &msg {
-                    // ignore incoming value; just respond with current/default BPM
-                    self.send_bpm(120.0).await?;
-                }
-                return Ok(msg);
-            }
+    /// Receive every message TLC sends from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<LiveMessage> {
+        self.messages.subscribe()
+    }
 
-            let mut buf = [0u8; 4096];
-            let n = timeout(Duration::from_secs(5), self.stream.read(&mut buf)).await??;
-            if n == 0 {
-                return Err(anyhow!("Connection closed"));
-            }
-            self.parser.feed(&buf[..n]);
+    async fn send(&mut self, cmd: &str) -> Result<()> {
+        write_line(&self.writer, cmd).await
+    }
+
+    /// Send a command whose reply the reader routes back
+    async fn request(&mut self, cmd: &str, kind: ReplyKind) -> Result<Reply> {
+        let (tx, rx) = oneshot::channel();
+        // Queue before writing so a fast reply can't beat its request into line
+        let id = self.pending.lock().unwrap().push(kind, tx);
+        if let Err(e) = self.send(cmd).await {
+            self.pending.lock().unwrap().remove(id);
+            return Err(e);
         }
+        Ok(Reply(rx))
     }
 
     /// Send BPM command - does not expect a response
@@ -255,16 +376,15 @@ impl LightingControllerClient {
         self.send("AUTO_BPM_OFF").await
     }
 
-    /// Request and retrieve button list
-    pub async fn button_list(&mut self) -> Result<Vec<Button>> {
-        self.send("BUTTON_LIST").await?;
-        loop {
-            match self.read_message().await? {
-                LiveMessage::ButtonList(list) => return Ok(list),
-                LiveMessage::Error(e) => return Err(anyhow!("Error: {}", e)),
-                _ => continue,
-            }
-        }
+    /// Send FADER_CHANGE command - does not expect a response
+    pub async fn send_fader(&mut self, index: u32, value: i32) -> Result<()> {
+        self.send(&format!("FADER_CHANGE|{}|{}", index, value)).await
+    }
+
+    /// Send a button list request without waiting for the answer, so the
+    /// client is free for other commands in the meantime
+    pub async fn request_button_list(&mut self) -> Result<Reply> {
+        self.request("BUTTON_LIST", ReplyKind::ButtonList).await
     }
 
     pub async fn button_press(&mut self, name: &str) -> Result<()> {
@@ -279,3 +399,110 @@ impl LightingControllerClient {
         self.send(&format!("CUE|{}", name)).await
     }
 }
+
+/// A reply that has been asked for but may not have arrived yet
+pub struct Reply(oneshot::Receiver<LiveMessage>);
+
+impl Reply {
+    async fn wait(self, limit: Duration) -> Result<LiveMessage> {
+        match timeout(limit, self.0).await {
+            Ok(Ok(reply)) => Ok(reply),
+            Ok(Err(_)) => Err(anyhow!("Connection closed")),
+            Err(_) => Err(anyhow!("No reply within {} ms", limit.as_millis())),
+        }
+    }
+
+    pub async fn button_list(self, limit: Duration) -> Result<Vec<Button>> {
+        match self.wait(limit).await? {
+            LiveMessage::ButtonList(list) => Ok(list),
+            other => Err(anyhow!("Unexpected reply: {:?}", other)),
+        }
+    }
+}
+
+impl Drop for LightingControllerClient {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+async fn write_line(writer: &Mutex<OwnedWriteHalf>, cmd: &str) -> Result<()> {
+    writer
+        .lock()
+        .await
+        .write_all(format!("{}\r\n", cmd).as_bytes())
+        .await?;
+    Ok(())
+}
+
+/// Read the socket until it closes, routing replies and publishing every message
+async fn read_loop(
+    mut reader: OwnedReadHalf,
+    mut parser: LiveParser,
+    writer: Arc<Mutex<OwnedWriteHalf>>,
+    messages: broadcast::Sender<LiveMessage>,
+    pending: PendingReplies,
+    tempo: SharedTempo,
+    // Dropped on return, which resolves `closed()`
+    _closed: watch::Sender<()>,
+) {
+    let mut buf = [0u8; 4096];
+    loop {
+        while let Some(msg) = parser.next_message() {
+            // Keep what the server reports, then answer with the selected tempo
+            // source; when that is the server itself, a reply would only echo it back
+            if let LiveMessage::Bpm(bpm) = &msg {
+                tempo.report(TempoSource::Controller, *bpm);
+                if tempo.settings().source != TempoSource::Controller {
+                    let _ = write_line(&writer, &format!("BPM|{}", tempo.bpm())).await;
+                }
+            }
+
+            pending.lock().unwrap().answer(&msg);
+
+            // Nobody listening is fine
+            let _ = messages.send(msg);
+        }
+
+        match reader.read(&mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => parser.feed(&buf[..n]),
+        }
+    }
+
+    // Fail whatever is still waiting for a reply
+    pending.lock().unwrap().waiting.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_lines_are_not_taken_as_replies() {
+        let mut pending = PendingState::default();
+        let (tx, mut rx) = oneshot::channel();
+        pending.push(ReplyKind::ButtonList, tx);
+
+        pending.answer(&LiveMessage::Error("BAD_COMMAND".to_string()));
+        assert!(rx.try_recv().is_err());
+
+        pending.answer(&LiveMessage::ButtonList(Vec::new()));
+        assert!(matches!(rx.try_recv(), Ok(LiveMessage::ButtonList(_))));
+    }
+
+    #[test]
+    fn stale_requests_are_dropped() {
+        let mut pending = PendingState::default();
+        let (stale_tx, mut stale_rx) = oneshot::channel();
+        pending.push(ReplyKind::ButtonList, stale_tx);
+        pending.waiting[0].sent -= REPLY_TIMEOUT;
+        let (tx, mut rx) = oneshot::channel();
+        pending.push(ReplyKind::ButtonList, tx);
+
+        pending.answer(&LiveMessage::ButtonList(Vec::new()));
+        assert!(stale_rx.try_recv().is_err());
+        assert!(matches!(rx.try_recv(), Ok(LiveMessage::ButtonList(_))));
+        assert!(pending.waiting.is_empty());
+    }
+}
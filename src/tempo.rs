@@ -0,0 +1,235 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::models::{MidiMessage, TempoSettings, TempoSource};
+
+/// MIDI Clock resolution in pulses per quarter note
+const CLOCK_PPQN: usize = 24;
+
+/// Number of recent tap intervals averaged into the tap tempo
+const TAP_INTERVALS: usize = 4;
+
+/// Pause after which taps start a new measurement
+const TAP_RESET: Duration = Duration::from_secs(2);
+
+/// Intervals further than this fraction from the median are ignored
+const TAP_OUTLIER_TOLERANCE: f32 = 0.25;
+
+/// Smallest MIDI Clock tempo change worth resending, so clock jitter doesn't flood the controller
+pub const BPM_RESEND_THRESHOLD: f32 = 0.5;
+
+/// Events produced by the MIDI Clock tracker
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClockEvent {
+    /// A quarter note boundary while the transport is running
+    Beat,
+    Started,
+    Stopped,
+}
+
+/// Derives a smoothed tempo and quarter-note beats from MIDI Clock
+pub struct MidiClockTracker {
+    running: bool,
+    /// Position within the current quarter note
+    tick_count: usize,
+    ticks: VecDeque<Instant>,
+    bpm: Option<f32>,
+}
+
+impl MidiClockTracker {
+    pub fn new() -> Self {
+        Self {
+            running: false,
+            tick_count: 0,
+            ticks: VecDeque::with_capacity(CLOCK_PPQN + 1),
+            bpm: None,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Smoothed tempo, once a full quarter note of clock has been received
+    pub fn bpm(&self) -> Option<f32> {
+        self.bpm
+    }
+
+    pub fn feed(&mut self, msg: &MidiMessage, now: Instant) -> Option<ClockEvent> {
+        match msg {
+            MidiMessage::Start => {
+                // The first clock after Start is the downbeat
+                self.running = true;
+                self.tick_count = 0;
+                Some(ClockEvent::Started)
+            }
+            MidiMessage::Continue => {
+                self.running = true;
+                Some(ClockEvent::Started)
+            }
+            MidiMessage::Stop => {
+                self.running = false;
+                Some(ClockEvent::Stopped)
+            }
+            MidiMessage::Clock => self.tick(now),
+            _ => None,
+        }
+    }
+
+    fn tick(&mut self, now: Instant) -> Option<ClockEvent> {
+        // A long gap means the clock source went away, so start measuring afresh
+        if let Some(last) = self.ticks.back() {
+            if now.duration_since(*last) > Duration::from_secs(1) {
+                self.ticks.clear();
+                self.bpm = None;
+            }
+        }
+
+        self.ticks.push_back(now);
+        if self.ticks.len() > CLOCK_PPQN + 1 {
+            self.ticks.pop_front();
+        }
+
+        // Measuring across a whole quarter note keeps per-tick jitter small,
+        // then a light moving average smooths what remains
+        if self.ticks.len() == CLOCK_PPQN + 1 {
+            let quarter = now.duration_since(self.ticks[0]).as_secs_f32();
+            if quarter > 0.0 {
+                let measured = 60.0 / quarter;
+                self.bpm = Some(match self.bpm {
+                    Some(bpm) => bpm + (measured - bpm) * 0.2,
+                    None => measured,
+                });
+            }
+        }
+
+        if !self.running {
+            return None;
+        }
+
+        let is_beat = self.tick_count == 0;
+        self.tick_count = (self.tick_count + 1) % CLOCK_PPQN;
+        is_beat.then_some(ClockEvent::Beat)
+    }
+}
+
+/// Turns repeated taps into a tempo measurement
+pub struct TapTempo {
+    taps: VecDeque<Instant>,
+}
+
+impl TapTempo {
+    pub fn new() -> Self {
+        Self {
+            taps: VecDeque::with_capacity(TAP_INTERVALS + 1),
+        }
+    }
+
+    /// Register a tap, returning the tempo once at least two taps are in
+    pub fn tap(&mut self, now: Instant) -> Option<f32> {
+        if let Some(last) = self.taps.back() {
+            if now.duration_since(*last) > TAP_RESET {
+                self.taps.clear();
+            }
+        }
+
+        self.taps.push_back(now);
+        if self.taps.len() > TAP_INTERVALS + 1 {
+            self.taps.pop_front();
+        }
+
+        let mut intervals: Vec<f32> = self
+            .taps
+            .iter()
+            .zip(self.taps.iter().skip(1))
+            .map(|(a, b)| b.duration_since(*a).as_secs_f32())
+            .collect();
+        if intervals.is_empty() {
+            return None;
+        }
+
+        // Reject outliers such as a double hit or a missed tap
+        let mut sorted = intervals.clone();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let median = sorted[sorted.len() / 2];
+        intervals.retain(|i| (i - median).abs() <= median * TAP_OUTLIER_TOLERANCE);
+
+        let average = intervals.iter().sum::<f32>() / intervals.len() as f32;
+        (average > 0.0).then(|| 60.0 / average)
+    }
+}
+
+/// The app's current tempo, shared between the UI and the controller
+/// connection so BPM requests from ShowXpress are answered from the
+/// selected source
+#[derive(Clone, Default)]
+pub struct SharedTempo {
+    inner: Arc<Mutex<TempoState>>,
+}
+
+#[derive(Default)]
+struct TempoState {
+    settings: TempoSettings,
+    /// Latest BPM from each source that reports one
+    readings: HashMap<TempoSource, f32>,
+}
+
+impl SharedTempo {
+    pub fn settings(&self) -> TempoSettings {
+        self.inner.lock().unwrap().settings
+    }
+
+    pub fn set_settings(&self, settings: TempoSettings) {
+        self.inner.lock().unwrap().settings = settings;
+    }
+
+    /// Record a new BPM from a source, whether or not it is the selected one.
+    /// Returns whether it should be sent on to ShowXpress: tap tempo and MIDI
+    /// Clock always are, since the selected source only decides how the
+    /// controller's BPM requests are answered.
+    pub fn report(&self, source: TempoSource, bpm: f32) -> bool {
+        self.inner.lock().unwrap().readings.insert(source, bpm);
+        source != TempoSource::Controller
+    }
+
+    /// Latest BPM from a source; the manual source always has one
+    pub fn reading(&self, source: TempoSource) -> Option<f32> {
+        let state = self.inner.lock().unwrap();
+        match source {
+            TempoSource::Manual => Some(state.settings.manual_bpm),
+            _ => state.readings.get(&source).copied(),
+        }
+    }
+
+    /// BPM of the selected source, falling back to the manual value until it has a reading
+    pub fn bpm(&self) -> f32 {
+        let settings = self.settings();
+        self.reading(settings.source).unwrap_or(settings.manual_bpm)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn taps_and_clock_are_sent_on_with_the_default_settings() {
+        let tempo = SharedTempo::default();
+        let manual_bpm = TempoSettings::default().manual_bpm;
+
+        assert!(tempo.report(TempoSource::TapTempo, 90.0));
+        assert!(tempo.report(TempoSource::MidiClock, 128.0));
+        assert!(!tempo.report(TempoSource::Controller, 100.0));
+
+        // BPM requests are still answered from the selected source
+        assert_eq!(tempo.bpm(), manual_bpm);
+        assert_eq!(tempo.reading(TempoSource::TapTempo), Some(90.0));
+
+        tempo.set_settings(TempoSettings {
+            source: TempoSource::MidiClock,
+            ..TempoSettings::default()
+        });
+        assert_eq!(tempo.bpm(), 128.0);
+    }
+}
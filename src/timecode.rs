@@ -0,0 +1,251 @@
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+
+use crate::models::MidiMessage;
+
+/// SMPTE frame rates as encoded in MTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameRate {
+    Fps24,
+    Fps25,
+    Fps2997Drop,
+    Fps30,
+}
+
+impl FrameRate {
+    fn from_code(code: u8) -> Self {
+        match code & 0x03 {
+            0 => FrameRate::Fps24,
+            1 => FrameRate::Fps25,
+            2 => FrameRate::Fps2997Drop,
+            _ => FrameRate::Fps30,
+        }
+    }
+
+    /// Frames counted per timecode second
+    pub fn nominal_fps(&self) -> u32 {
+        match self {
+            FrameRate::Fps24 => 24,
+            FrameRate::Fps25 => 25,
+            FrameRate::Fps2997Drop | FrameRate::Fps30 => 30,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FrameRate::Fps24 => "24 fps",
+            FrameRate::Fps25 => "25 fps",
+            FrameRate::Fps2997Drop => "29.97 drop",
+            FrameRate::Fps30 => "30 fps",
+        }
+    }
+}
+
+/// SMPTE timecode position
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Timecode {
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+    pub frames: u8,
+}
+
+impl Timecode {
+    /// Parse `HH:MM:SS:FF` (`;` is accepted as the frame separator for drop-frame)
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = text.trim().split([':', ';']).collect();
+        if parts.len() != 4 {
+            return Err("Timecode must be in HH:MM:SS:FF format".to_string());
+        }
+
+        let mut values = [0u8; 4];
+        for (value, part) in values.iter_mut().zip(&parts) {
+            *value = part
+                .parse()
+                .map_err(|_| format!("Invalid timecode field: {}", part))?;
+        }
+
+        let [hours, minutes, seconds, frames] = values;
+        if hours > 23 || minutes > 59 || seconds > 59 || frames > 29 {
+            return Err(format!("Timecode out of range: {}", text.trim()));
+        }
+
+        Ok(Self { hours, minutes, seconds, frames })
+    }
+
+    /// Absolute frame count, used to order positions at a given rate
+    pub fn to_frames(self, rate: FrameRate) -> u64 {
+        let total_seconds =
+            (self.hours as u64 * 60 + self.minutes as u64) * 60 + self.seconds as u64;
+        total_seconds * rate.nominal_fps() as u64 + self.frames as u64
+    }
+
+    fn from_frames(frames: u64, rate: FrameRate) -> Self {
+        let fps = rate.nominal_fps() as u64;
+        let total_seconds = frames / fps;
+        Self {
+            hours: ((total_seconds / 3600) % 24) as u8,
+            minutes: ((total_seconds / 60) % 60) as u8,
+            seconds: (total_seconds % 60) as u8,
+            frames: (frames % fps) as u8,
+        }
+    }
+
+    /// Decode a full-frame message `F0 7F <device> 01 01 hh mm ss ff F7`
+    pub fn from_full_frame(data: &[u8]) -> Option<(Self, FrameRate)> {
+        match data {
+            [0xF0, 0x7F, _device, 0x01, 0x01, hr, mn, sc, fr, 0xF7] => Some((
+                Self {
+                    hours: hr & 0x1F,
+                    minutes: mn & 0x3F,
+                    seconds: sc & 0x3F,
+                    frames: fr & 0x1F,
+                },
+                FrameRate::from_code(hr >> 5),
+            )),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Timecode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}:{:02}:{:02}",
+            self.hours, self.minutes, self.seconds, self.frames
+        )
+    }
+}
+
+/// Assembles MTC quarter-frame and full-frame messages into a running position
+pub struct MtcDecoder {
+    pieces: [u8; 8],
+    received: u8,
+}
+
+impl MtcDecoder {
+    pub fn new() -> Self {
+        Self {
+            pieces: [0; 8],
+            received: 0,
+        }
+    }
+
+    /// Feed a MIDI message, returning the new position when it changes
+    pub fn feed(&mut self, msg: &MidiMessage) -> Option<(Timecode, FrameRate)> {
+        match msg {
+            MidiMessage::MtcQuarterFrame { piece, value } => {
+                let piece = (*piece & 0x07) as usize;
+                if piece == 0 {
+                    self.received = 0;
+                }
+                self.pieces[piece] = value & 0x0F;
+                self.received |= 1 << piece;
+
+                if piece != 7 || self.received != 0xFF {
+                    return None;
+                }
+
+                let p = &self.pieces;
+                let timecode = Timecode {
+                    hours: p[6] | ((p[7] & 0x01) << 4),
+                    minutes: p[4] | ((p[5] & 0x03) << 4),
+                    seconds: p[2] | ((p[3] & 0x03) << 4),
+                    frames: p[0] | ((p[1] & 0x01) << 4),
+                };
+                let rate = FrameRate::from_code(p[7] >> 1);
+
+                // A full quarter-frame cycle spans two frames, so the assembled
+                // value is two frames behind the sender by the time it completes
+                let frames = timecode.to_frames(rate) + 2;
+                Some((Timecode::from_frames(frames, rate), rate))
+            }
+            MidiMessage::SysEx(data) => {
+                let full_frame = Timecode::from_full_frame(data)?;
+                self.received = 0;
+                Some(full_frame)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Tracks incoming timecode to decide which cue stamps have been crossed.
+///
+/// Small forward steps fire every cue in between. Backward steps and large
+/// forward jumps (scrubbing, locates) only relocate, so nothing re-fires;
+/// a cue on the frame playback starts from fires once it rolls forward.
+pub struct TimecodeChaser {
+    last_frame: Option<u64>,
+    /// Whether `last_frame` was relocated to rather than played through
+    relocated: bool,
+}
+
+impl TimecodeChaser {
+    pub fn new() -> Self {
+        Self {
+            last_frame: None,
+            relocated: true,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.last_frame = None;
+        self.relocated = true;
+    }
+
+    /// Advance to a new position. Returns the crossed frame window
+    /// `(previous, current]` when timecode is playing forward, or
+    /// `[previous, current]` on the first step after a relocate.
+    pub fn advance(&mut self, timecode: Timecode, rate: FrameRate) -> Option<RangeInclusive<u64>> {
+        let frame = timecode.to_frames(rate);
+        let previous = self.last_frame.replace(frame)?;
+        if frame == previous {
+            return None;
+        }
+
+        // Anything more than a second ahead is treated as a jump rather than playback
+        let max_step = rate.nominal_fps() as u64;
+        if frame > previous && frame - previous <= max_step {
+            let from = if self.relocated { previous } else { previous + 1 };
+            self.relocated = false;
+            Some(from..=frame)
+        } else {
+            self.relocated = true;
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: FrameRate = FrameRate::Fps25;
+
+    fn at(chaser: &mut TimecodeChaser, frame: u64) -> Option<RangeInclusive<u64>> {
+        chaser.advance(Timecode::from_frames(frame, RATE), RATE)
+    }
+
+    #[test]
+    fn cue_at_frame_zero_fires_when_playback_starts() {
+        let mut chaser = TimecodeChaser::new();
+        assert_eq!(at(&mut chaser, 0), None);
+        assert_eq!(at(&mut chaser, 1), Some(0..=1));
+        assert_eq!(at(&mut chaser, 2), Some(2..=2));
+    }
+
+    #[test]
+    fn cue_at_locate_target_fires_once_playback_resumes() {
+        let mut chaser = TimecodeChaser::new();
+        at(&mut chaser, 10);
+        assert_eq!(at(&mut chaser, 11), Some(10..=11));
+
+        // Locate well past the current position, park there, then roll on
+        assert_eq!(at(&mut chaser, 500), None);
+        assert_eq!(at(&mut chaser, 500), None);
+        assert_eq!(at(&mut chaser, 501), Some(500..=501));
+        assert_eq!(at(&mut chaser, 502), Some(502..=502));
+    }
+}
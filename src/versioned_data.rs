@@ -52,6 +52,94 @@ impl Migration for PresetMigrationV0ToV1 {
     }
 }
 
+struct PresetMigrationV1ToV2;
+
+impl Migration for PresetMigrationV1ToV2 {
+    fn migrate(&self, from_version: u32, mut data: Value) -> Result<Value> {
+        match from_version {
+            1 => {
+                // Version 1: CC trigger values were a single exact number and
+                // Note On triggers had no velocity range
+                let obj = data
+                    .as_object_mut()
+                    .ok_or_else(|| anyhow::anyhow!("Expected presets object in version 1 data"))?;
+                if let Some(presets) = obj.get_mut("presets").and_then(Value::as_array_mut) {
+                    for preset in presets {
+                        if let Some(triggers) = preset.get_mut("triggers").and_then(Value::as_array_mut) {
+                            triggers.iter_mut().for_each(migrate_trigger_v1_to_v2);
+                        }
+                    }
+                }
+                obj.insert("version".to_string(), json!(2));
+                Ok(data)
+            }
+            _ => Err(anyhow::anyhow!("Unknown source version for preset migration: {}", from_version)),
+        }
+    }
+
+    fn target_version(&self) -> u32 {
+        2
+    }
+}
+
+struct PresetMigrationV2ToV3;
+
+impl Migration for PresetMigrationV2ToV3 {
+    fn migrate(&self, from_version: u32, mut data: Value) -> Result<Value> {
+        match from_version {
+            2 => {
+                // Version 2: every action was a button action with the button
+                // name and action type stored directly on it
+                let obj = data
+                    .as_object_mut()
+                    .ok_or_else(|| anyhow::anyhow!("Expected presets object in version 2 data"))?;
+                if let Some(presets) = obj.get_mut("presets").and_then(Value::as_array_mut) {
+                    for preset in presets {
+                        if let Some(actions) = preset.get_mut("actions").and_then(Value::as_array_mut) {
+                            for action in actions {
+                                migrate_action_v2_to_v3(action)?;
+                            }
+                        }
+                    }
+                }
+                obj.insert("version".to_string(), json!(3));
+                Ok(data)
+            }
+            _ => Err(anyhow::anyhow!("Unknown source version for preset migration: {}", from_version)),
+        }
+    }
+
+    fn target_version(&self) -> u32 {
+        3
+    }
+}
+
+/// Convert a version 1 trigger to use value ranges
+fn migrate_trigger_v1_to_v2(trigger: &mut Value) {
+    if let Some(cc) = trigger.get_mut("ControlChange").and_then(Value::as_object_mut) {
+        if let Some(value) = cc.get("value").and_then(Value::as_u64) {
+            cc.insert("value".to_string(), json!({ "min": value, "max": value }));
+        }
+    }
+    if let Some(note_on) = trigger.get_mut("NoteOn").and_then(Value::as_object_mut) {
+        note_on.entry("velocity").or_insert(Value::Null);
+    }
+}
+
+/// Wrap a version 2 button action in the controller command enum
+fn migrate_action_v2_to_v3(action: &mut Value) -> Result<()> {
+    let obj = action
+        .as_object_mut()
+        .ok_or_else(|| anyhow::anyhow!("Expected action object in version 2 data"))?;
+    let button_name = obj.remove("button_name").unwrap_or(Value::Null);
+    let action_type = obj.remove("action").unwrap_or(Value::Null);
+    obj.insert(
+        "command".to_string(),
+        json!({ "Button": { "button_name": button_name, "action": action_type } }),
+    );
+    Ok(())
+}
+
 // ============================================================================
 // Versioned AppConfig
 // ============================================================================
@@ -104,6 +192,53 @@ impl Migration for ConfigMigrationV0ToV1 {
     }
 }
 
+struct ConfigMigrationV1ToV2;
+
+impl Migration for ConfigMigrationV1ToV2 {
+    fn migrate(&self, from_version: u32, mut data: Value) -> Result<Value> {
+        match from_version {
+            1 => {
+                // Version 1: the tap tempo binding uses the old trigger format
+                let obj = data
+                    .as_object_mut()
+                    .ok_or_else(|| anyhow::anyhow!("Expected config object in version 1 data"))?;
+                if let Some(trigger) = obj.get_mut("tap_tempo_trigger") {
+                    migrate_trigger_v1_to_v2(trigger);
+                }
+                obj.insert("version".to_string(), json!(2));
+                Ok(data)
+            }
+            _ => Err(anyhow::anyhow!("Unknown source version for config migration: {}", from_version)),
+        }
+    }
+
+    fn target_version(&self) -> u32 {
+        2
+    }
+}
+
+struct ConfigMigrationV2ToV3;
+
+impl Migration for ConfigMigrationV2ToV3 {
+    fn migrate(&self, from_version: u32, mut data: Value) -> Result<Value> {
+        match from_version {
+            2 => {
+                // Version 3 only changed the preset action format; the config just moves along
+                let obj = data
+                    .as_object_mut()
+                    .ok_or_else(|| anyhow::anyhow!("Expected config object in version 2 data"))?;
+                obj.insert("version".to_string(), json!(3));
+                Ok(data)
+            }
+            _ => Err(anyhow::anyhow!("Unknown source version for config migration: {}", from_version)),
+        }
+    }
+
+    fn target_version(&self) -> u32 {
+        3
+    }
+}
+
 // ============================================================================
 // Migration Helpers
 // ============================================================================
@@ -112,6 +247,8 @@ impl Migration for ConfigMigrationV0ToV1 {
 fn get_preset_migrations() -> Vec<Box<dyn Migration>> {
     vec![
         Box::new(PresetMigrationV0ToV1),
+        Box::new(PresetMigrationV1ToV2),
+        Box::new(PresetMigrationV2ToV3),
     ]
 }
 
@@ -119,6 +256,8 @@ fn get_preset_migrations() -> Vec<Box<dyn Migration>> {
 fn get_config_migrations() -> Vec<Box<dyn Migration>> {
     vec![
         Box::new(ConfigMigrationV0ToV1),
+        Box::new(ConfigMigrationV1ToV2),
+        Box::new(ConfigMigrationV2ToV3),
     ]
 }
 
@@ -154,3 +293,106 @@ pub fn save_config(config: &AppConfig) -> Result<String> {
         .map_err(|e| anyhow::anyhow!("Failed to serialize config: {}", e))
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ButtonActionType, ControllerAction, MidiTrigger, ValueRange};
+
+    fn legacy_preset(triggers: Value) -> Value {
+        json!({
+            "id": "6f1c2a9e-1f4b-4d8e-9b1a-2f3c4d5e6f70",
+            "name": "Strobe",
+            "description": "",
+            "triggers": triggers,
+            "actions": [
+                { "button_name": "Strobe", "action": "Toggle", "delay_secs": 0.5 }
+            ],
+            "delay_secs": 0.1
+        })
+    }
+
+    fn assert_legacy_action_migrated(preset: &Preset) {
+        assert_eq!(preset.actions.len(), 1);
+        assert_eq!(
+            preset.actions[0].command,
+            ControllerAction::Button {
+                button_name: "Strobe".to_string(),
+                action: ButtonActionType::Toggle,
+            }
+        );
+        assert_eq!(preset.actions[0].delay_secs, 0.5);
+    }
+
+    #[test]
+    fn v1_presets_get_value_ranges_and_button_commands() {
+        let triggers = json!([
+            { "ControlChange": { "channel": 0, "cc": 7, "value": 64 } },
+            { "NoteOn": { "channel": 1, "note": 60 } }
+        ]);
+        let data = json!({ "version": 1, "presets": [legacy_preset(triggers)] });
+
+        let (presets, migrated_from) = load_presets(&data.to_string()).unwrap();
+        assert_eq!(migrated_from, Some(1));
+        assert_eq!(
+            presets[0].triggers,
+            vec![
+                MidiTrigger::ControlChange {
+                    channel: Some(0),
+                    cc: 7,
+                    value: Some(ValueRange { min: 64, max: 64 }),
+                    threshold: None,
+                },
+                MidiTrigger::NoteOn {
+                    channel: Some(1),
+                    note: 60,
+                    velocity: None,
+                    gesture: None,
+                },
+            ]
+        );
+        assert_legacy_action_migrated(&presets[0]);
+    }
+
+    #[test]
+    fn v2_presets_get_button_commands() {
+        let triggers = json!([
+            { "ControlChange": { "channel": 0, "cc": 7, "value": { "min": 0, "max": 10 } } }
+        ]);
+        let data = json!({ "version": 2, "presets": [legacy_preset(triggers)] });
+
+        let (presets, migrated_from) = load_presets(&data.to_string()).unwrap();
+        assert_eq!(migrated_from, Some(2));
+        assert!(matches!(
+            presets[0].triggers[0],
+            MidiTrigger::ControlChange { value: Some(ValueRange { min: 0, max: 10 }), .. }
+        ));
+        assert_legacy_action_migrated(&presets[0]);
+    }
+
+    #[test]
+    fn v1_config_tap_tempo_binding_gets_a_value_range() {
+        let data = json!({
+            "version": 1,
+            "last_midi_port": "Pads",
+            "last_controller_address": "10.0.0.5:7348",
+            "last_controller_password": null,
+            "last_action_type": "Press",
+            "tap_tempo_trigger": { "ControlChange": { "channel": 2, "cc": 20, "value": 127 } }
+        });
+
+        let (config, migrated_from) = load_config(&data.to_string()).unwrap();
+        assert_eq!(migrated_from, Some(1));
+        assert_eq!(config.last_midi_port.as_deref(), Some("Pads"));
+        assert_eq!(config.last_action_type, Some(ButtonActionType::Press));
+        assert_eq!(
+            config.tap_tempo_trigger,
+            Some(MidiTrigger::ControlChange {
+                channel: Some(2),
+                cc: 20,
+                value: Some(ValueRange { min: 127, max: 127 }),
+                threshold: None,
+            })
+        );
+    }
+}
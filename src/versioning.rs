@@ -2,7 +2,7 @@ use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
 /// Current version of the data format
-pub const CURRENT_VERSION: u32 = 1;
+pub const CURRENT_VERSION: u32 = 3;
 
 /// Trait for migration functions
 pub trait Migration: Send + Sync {